@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::remote::{DbtCloudApi, Run};
+
+pub const DEFAULT_ARTIFACTS: [&str; 3] = ["manifest.json", "run_results.json", "catalog.json"];
+
+/// The run to download from: the one asked for, or the job's latest successful run
+pub fn find_run(
+    api: &dyn DbtCloudApi,
+    job_id: i64,
+    run_id: Option<i64>,
+) -> Result<Run, Box<dyn Error>> {
+    match run_id {
+        Some(run_id) => {
+            let run = api
+                .get_run(run_id)?
+                .data
+                .ok_or_else(|| format!("no run with id {}", run_id))?;
+            if run.job_definition_id != job_id {
+                return Err(format!("run {} doesn't belong to job {}", run_id, job_id).into());
+            }
+            Ok(run)
+        }
+        None => api
+            .get_runs(job_id, Some(Run::SUCCESS), 1)?
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("job {} has no successful runs", job_id).into()),
+    }
+}
+
+/// Downloads each artifact into `out`, skipping the ones the run didn't produce.
+/// Returns the paths that were written.
+pub fn download(
+    api: &dyn DbtCloudApi,
+    run: &Run,
+    artifacts: &[String],
+    out: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut written = vec![];
+    for artifact in artifacts {
+        let contents = match api.get_run_artifact(run.id, artifact) {
+            Ok(c) => c,
+            Err(err) => {
+                println!("{}", format!("skipping {}: {}", artifact, err).yellow());
+                continue;
+            }
+        };
+        let path = out.join(artifact);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        println!("downloaded {}", path.display());
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{download, find_run};
+    use crate::remote::{DbtCloudApi, FakeDbtCloud, Job, TriggerRun};
+
+    #[test]
+    fn latest_successful_run() {
+        let fake = FakeDbtCloud::new(123);
+        let job = Job {
+            account_id: 123,
+            project_id: 456,
+            name: "Full Run".to_string(),
+            ..Job::default()
+        };
+        let job_id = fake.create_job(&job).unwrap().data.unwrap().id.unwrap();
+        assert!(find_run(&fake, job_id, None).is_err());
+
+        for _ in 0..2 {
+            fake.trigger_run(job_id, &TriggerRun::default()).unwrap();
+        }
+        let run = find_run(&fake, job_id, None).unwrap();
+        assert_eq!(run.id, 2);
+        assert_eq!(find_run(&fake, job_id, Some(1)).unwrap().id, 1);
+        assert!(find_run(&fake, job_id + 1, Some(1)).is_err());
+
+        fake.add_artifact(run.id, "manifest.json", "{\"nodes\": {}}");
+        let out = std::env::temp_dir().join(format!("dbterra-artifacts-{}", std::process::id()));
+        let written = download(
+            &fake,
+            &run,
+            &["manifest.json".to_string(), "catalog.json".to_string()],
+            &out,
+        )
+        .unwrap();
+        assert_eq!(written, vec![out.join("manifest.json")]);
+        assert_eq!(fs::read_to_string(&written[0]).unwrap(), "{\"nodes\": {}}");
+        fs::remove_dir_all(out).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, TimeDelta};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{lookup::managed_jobs, remote::Run, schedule::ScheduledJob, workspace::Workspace};
+
+/// The key run slots are saved under when dbt_cloud.yml doesn't use `accounts:`
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Typical run times and run slots, as fetched from dbt Cloud or saved with `--save`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Durations {
+    /// By the key under `accounts:`
+    pub run_slots: BTreeMap<String, i64>,
+    /// Seconds by job, e.g. `analytics.nightly` or `eu/analytics.nightly`
+    pub jobs: BTreeMap<String, i64>,
+}
+
+impl Durations {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| format!("failed to parse {}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, contents + "\n")
+            .map_err(|err| format!("failed to write {}: {}", path.display(), err))
+    }
+
+    pub fn extend(&mut self, other: Durations) {
+        self.run_slots.extend(other.run_slots);
+        self.jobs.extend(other.jobs);
+    }
+}
+
+/// The account's run slots and the median duration of the `limit` most recent finished runs
+/// of every job in dbt_cloud.yml. Jobs that never ran are left out.
+pub fn collect(workspace: &Workspace, limit: usize) -> Result<Durations, Box<dyn Error>> {
+    let account = workspace.client.get_account()?.data.ok_or("no account")?;
+    let remote_jobs = workspace.client.get_jobs()?.data.unwrap_or_default();
+    let mut jobs = BTreeMap::new();
+    for managed in managed_jobs(&workspace.yaml, workspace.config) {
+        let remote = remote_jobs
+            .iter()
+            .find(|j| j.project_id == managed.job.project_id && j.name == managed.job.name);
+        let Some(id) = remote.and_then(|r| r.id) else {
+            continue;
+        };
+        let runs = workspace.client.get_runs(id, None, limit)?.data;
+        let mut seconds: Vec<_> = runs.iter().flatten().filter_map(duration).collect();
+        if seconds.is_empty() {
+            continue;
+        }
+        seconds.sort();
+        let key = format!("{}.{}", managed.project_key, managed.key);
+        jobs.insert(workspace.prefix(&key), seconds[seconds.len() / 2]);
+    }
+    let name = workspace.name.as_deref().unwrap_or(DEFAULT_ACCOUNT);
+    Ok(Durations {
+        run_slots: BTreeMap::from([(name.to_string(), account.run_slots)]),
+        jobs,
+    })
+}
+
+/// How long a finished run took, in seconds
+fn duration(run: &Run) -> Option<i64> {
+    if !run.is_complete() {
+        return None;
+    }
+    let started = parse_timestamp(run.started_at.as_ref()?)?;
+    let finished = parse_timestamp(run.finished_at.as_ref()?)?;
+    Some((finished - started).num_seconds().max(0))
+}
+
+/// dbt Cloud writes `2024-05-06 09:00:03.123456+00:00`
+fn parse_timestamp(value: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z")
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .ok()
+        .map(|t| t.naive_utc())
+}
+
+/// A stretch of time when runs wait for a free run slot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Congestion {
+    pub start: NaiveDateTime,
+    /// The first minute nothing is waiting anymore
+    pub end: NaiveDateTime,
+    pub most_waiting: usize,
+    pub longest_wait: TimeDelta,
+    /// Jobs that ran or waited during it, the ones taking up the slots
+    pub jobs: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    pub account: Option<String>,
+    pub day: NaiveDate,
+    pub run_slots: i64,
+    pub congestion: Vec<Congestion>,
+    /// The most runs going or waiting at once
+    pub peak: usize,
+    /// Scheduled jobs without a known duration, left out of the simulation
+    pub unknown: Vec<String>,
+}
+
+struct Queued {
+    job: String,
+    fired: NaiveDateTime,
+    minutes: i64,
+}
+
+/// Plays `day` minute by minute, starting runs in the order they fire as long as there is a
+/// free run slot. The day before is played too so that long runs carry over into `day`.
+pub fn simulate(
+    account: Option<String>,
+    jobs: &[ScheduledJob],
+    durations: &Durations,
+    day: NaiveDate,
+    run_slots: i64,
+) -> Simulation {
+    let start = day.and_hms_opt(0, 0, 0).unwrap();
+    let from = start - Days::new(1);
+    let to = start + Days::new(1);
+    let mut unknown = vec![];
+    let mut fired: BTreeMap<NaiveDateTime, Vec<Queued>> = BTreeMap::new();
+    for job in jobs {
+        let name = job.name();
+        let Some(seconds) = durations.jobs.get(&name) else {
+            unknown.push(name);
+            continue;
+        };
+        // Whole minutes, a run always takes at least one
+        let minutes = ((seconds + 59) / 60).max(1);
+        for time in job.cron.fire_times(from, to) {
+            fired.entry(time).or_default().push(Queued {
+                job: name.clone(),
+                fired: time,
+                minutes,
+            });
+        }
+    }
+
+    let mut running: Vec<(String, NaiveDateTime)> = vec![];
+    let mut waiting: VecDeque<Queued> = VecDeque::new();
+    let mut congestion: Vec<Congestion> = vec![];
+    let mut current: Option<Congestion> = None;
+    let mut peak = 0;
+    let mut time = from;
+    while time < to {
+        running.retain(|(_, finish)| *finish > time);
+        waiting.extend(fired.remove(&time).unwrap_or_default());
+        while (running.len() as i64) < run_slots {
+            let Some(run) = waiting.pop_front() else {
+                break;
+            };
+            running.push((run.job, time + TimeDelta::minutes(run.minutes)));
+        }
+        if time >= start {
+            peak = peak.max(running.len() + waiting.len());
+            if let Some(first) = waiting.front() {
+                let c = current.get_or_insert_with(|| Congestion {
+                    start: time,
+                    end: time,
+                    most_waiting: 0,
+                    longest_wait: TimeDelta::zero(),
+                    jobs: BTreeSet::new(),
+                });
+                c.end = time + TimeDelta::minutes(1);
+                c.most_waiting = c.most_waiting.max(waiting.len());
+                // Still waiting a minute from now at least
+                c.longest_wait = c.longest_wait.max(c.end - first.fired);
+                c.jobs.extend(running.iter().map(|(job, _)| job.clone()));
+                c.jobs.extend(waiting.iter().map(|q| q.job.clone()));
+            } else if let Some(c) = current.take() {
+                congestion.push(c);
+            }
+        }
+        time += TimeDelta::minutes(1);
+    }
+    congestion.extend(current);
+    Simulation {
+        account,
+        day,
+        run_slots,
+        congestion,
+        peak,
+        unknown,
+    }
+}
+
+pub fn pretty_print(simulation: &Simulation) {
+    if let Some(account) = &simulation.account {
+        println!("{}", format!("account {}:\n", account).bold());
+    }
+    for job in &simulation.unknown {
+        println!(
+            "{} {} has no known run time, it's left out",
+            "warning:".yellow(),
+            job
+        );
+    }
+    if !simulation.unknown.is_empty() {
+        println!();
+    }
+    let day = simulation.day.format("%Y-%m-%d");
+    if simulation.congestion.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "no runs wait for a slot on {}, at most {} of {} run slots are needed",
+                day, simulation.peak, simulation.run_slots
+            )
+            .green()
+        );
+        return;
+    }
+    println!(
+        "{} with {} run slot(s), runs wait for a slot at:\n",
+        day, simulation.run_slots
+    );
+    for c in &simulation.congestion {
+        // The simulation stops at midnight, so a day that ends congested ends at 24:00
+        let end = if c.end.date() > simulation.day {
+            "24:00".to_string()
+        } else {
+            c.end.format("%H:%M").to_string()
+        };
+        println!(
+            "{}  up to {} waiting, for up to {} min",
+            format!("{}-{}", c.start.format("%H:%M"), end).red(),
+            c.most_waiting,
+            c.longest_wait.num_minutes()
+        );
+        for job in &c.jobs {
+            println!("      {}", job);
+        }
+    }
+    println!(
+        "\nat most {} runs going or waiting at once",
+        simulation.peak
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::NaiveDate;
+
+    use super::{collect, simulate, Durations};
+    use crate::{
+        local::Root,
+        remote::{DbtCloudApi, FakeDbtCloud, Job, Run},
+        schedule::scheduled_jobs,
+        testing::workspace,
+    };
+
+    fn root() -> Root {
+        serde_yaml::from_str(
+            "
+projects:
+  example:
+    id: 456
+    jobs:
+      nightly:
+        environment: prod
+        target: production
+        steps: [dbt build]
+        schedule:
+          cron: \"0 9 * * *\"
+      hourly:
+        environment: prod
+        target: production
+        steps: [dbt build]
+        schedule:
+          cron: \"0 * * * *\"
+      new:
+        environment: prod
+        target: production
+        steps: [dbt build]
+        schedule:
+          cron: \"30 9 * * *\"
+environments:
+  prod:
+    id: 789
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn runs_wait_for_slots() {
+        let jobs = scheduled_jobs(&root().split_accounts().unwrap()).unwrap();
+        let durations = Durations {
+            run_slots: BTreeMap::new(),
+            jobs: BTreeMap::from([
+                ("example.nightly".to_string(), 45 * 60),
+                ("example.hourly".to_string(), 10 * 60 + 1),
+            ]),
+        };
+        let day = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap();
+
+        let simulation = simulate(None, &jobs, &durations, day, 2);
+        assert!(simulation.congestion.is_empty());
+        assert_eq!(simulation.peak, 2);
+        assert_eq!(simulation.unknown, vec!["example.new"]);
+
+        // With one slot the nightly run waits for the hourly one, which goes first by key
+        let simulation = simulate(None, &jobs, &durations, day, 1);
+        assert_eq!(simulation.congestion.len(), 1);
+        let c = &simulation.congestion[0];
+        assert_eq!(c.start.format("%H:%M").to_string(), "09:00");
+        assert_eq!(c.end.format("%H:%M").to_string(), "09:11");
+        assert_eq!(c.longest_wait.num_minutes(), 11);
+        assert_eq!(
+            c.jobs.iter().collect::<Vec<_>>(),
+            vec!["example.hourly", "example.nightly"]
+        );
+    }
+
+    #[test]
+    fn durations_from_runs() {
+        let fake = FakeDbtCloud::new(123);
+        let job = Job {
+            account_id: 123,
+            project_id: 456,
+            name: "Nightly".to_string(),
+            ..Job::default()
+        };
+        let job_id = fake.create_job(&job).unwrap().data.unwrap().id.unwrap();
+        for (id, minutes) in [(1, 10), (2, 30), (3, 12)] {
+            fake.add_run(Run {
+                id,
+                account_id: 123,
+                project_id: 456,
+                job_definition_id: job_id,
+                status: Run::SUCCESS,
+                started_at: Some("2024-05-06 09:00:00.000000+00:00".to_string()),
+                finished_at: Some(format!("2024-05-06 09:{}:00.000000+00:00", minutes)),
+                ..Run::default()
+            });
+        }
+        let workspace = workspace(root(), &fake);
+        let durations = collect(&workspace, 10).unwrap();
+        assert_eq!(durations.run_slots["default"], 5);
+        assert_eq!(
+            durations.jobs,
+            BTreeMap::from([("example.nightly".to_string(), 12 * 60)])
+        );
+    }
+}
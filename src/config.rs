@@ -1,6 +1,8 @@
 use std::env;
 
 use crate::local::Root;
+use crate::profiles::{Profile, Profiles};
+use crate::remote::RetryPolicy;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -8,31 +10,72 @@ pub struct Config {
     pub project_id: Option<i64>,
     pub token: String,
     pub base_url: String,
+    pub retry: RetryPolicy,
 }
 
 const BASE_URL: &str = "DBT_CLOUD_BASE_URL";
 const ACCOUNT_ENV: &str = "DBT_CLOUD_ACCOUNT_ID";
 const TOKEN_ENV: &str = "DBT_CLOUD_TOKEN";
 
+/// Values given explicitly on the command line, they win over everything else
+#[derive(Default, Debug, Clone)]
+pub struct Overrides {
+    pub profile: Option<String>,
+    pub account_id: Option<i64>,
+    pub token: Option<String>,
+    pub base_url: Option<String>,
+}
+
 impl Config {
-    pub fn build(yaml: &Root) -> Result<Config, &'static str> {
-        let account_id = env::var(ACCOUNT_ENV).unwrap_or_else(|_| {
-            if let Some(account) = &yaml.account {
-                account.id.to_string()
-            } else {
-                panic!(
-                    "{} must be set or declared via `account` in dbt_cloud.yml",
-                    ACCOUNT_ENV
-                )
+    /// Each value comes from the first place it's set in: command line flags, the selected
+    /// profile, environment variables and finally dbt_cloud.yml
+    pub fn build(yaml: &Root, overrides: &Overrides) -> Result<Config, String> {
+        let profile = match &overrides.profile {
+            Some(name) => Profiles::load()?.get(name)?.clone(),
+            None => Profile::default(),
+        };
+        Config::from_profile(yaml, overrides, profile)
+    }
+
+    /// Like `build`, with the profile already picked
+    pub fn from_profile(
+        yaml: &Root,
+        overrides: &Overrides,
+        profile: Profile,
+    ) -> Result<Config, String> {
+        let account_id = match overrides.account_id.or(profile.account_id) {
+            Some(account_id) => account_id,
+            None => match env::var(ACCOUNT_ENV) {
+                Ok(account_id) => account_id
+                    .parse()
+                    .map_err(|_| "account_id must be a number".to_string())?,
+                Err(_) => yaml.account.as_ref().map(|a| a.id).ok_or_else(|| {
+                    format!(
+                        "{} must be set or declared via `account` in dbt_cloud.yml",
+                        ACCOUNT_ENV
+                    )
+                })?,
+            },
+        };
+        let token = match (&overrides.token, &profile.token) {
+            (Some(token), _) => token.to_string(),
+            (None, Some(token)) => token.resolve()?,
+            (None, None) => {
+                env::var(TOKEN_ENV).map_err(|_| format!("{} must be set", TOKEN_ENV))?
             }
-        });
-        let token = env::var(TOKEN_ENV).unwrap_or_else(|_| panic!("{} must be set", TOKEN_ENV));
-        let base_url = env::var(BASE_URL).unwrap_or_else(|_| "https://cloud.getdbt.com".to_string());
+        };
+        let base_url = overrides
+            .base_url
+            .clone()
+            .or(profile.base_url)
+            .or_else(|| env::var(BASE_URL).ok())
+            .unwrap_or_else(|| "https://cloud.getdbt.com".to_string());
         Ok(Config {
-            account_id: account_id.parse().expect("account_id must be a number"),
+            account_id,
             project_id: None,
             token,
             base_url,
+            retry: RetryPolicy::from_env()?,
         })
     }
     pub fn with_project_id(&self, project_id: i64) -> Self {
@@ -43,3 +86,41 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Overrides};
+    use crate::{local::Root, profiles::Profiles};
+
+    #[test]
+    fn flags_win_over_profile() {
+        let profiles: Profiles = serde_yaml::from_str(
+            "
+profiles:
+  eu:
+    base_url: https://xyz.eu1.dbt.com
+    account_id: 456
+    token:
+      value: from-profile
+",
+        )
+        .unwrap();
+        let profile = profiles.get("eu").unwrap();
+
+        let overrides = Overrides::default();
+        let config = Config::from_profile(&Root::default(), &overrides, profile.clone()).unwrap();
+        assert_eq!(config.account_id, 456);
+        assert_eq!(config.token, "from-profile");
+        assert_eq!(config.base_url, "https://xyz.eu1.dbt.com");
+
+        let overrides = Overrides {
+            account_id: Some(789),
+            token: Some("from-flag".to_string()),
+            ..overrides
+        };
+        let config = Config::from_profile(&Root::default(), &overrides, profile.clone()).unwrap();
+        assert_eq!(config.account_id, 789);
+        assert_eq!(config.token, "from-flag");
+        assert_eq!(config.base_url, "https://xyz.eu1.dbt.com");
+    }
+}
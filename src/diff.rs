@@ -1,7 +1,7 @@
 use colored::*;
 use serde_json::Value;
-use treediff::tools::{ChangeType, Recorder};
-use treediff::value::Key;
+
+use crate::fields::{matches_path, FieldNames};
 
 #[derive(Debug, Clone)]
 pub struct Diff {
@@ -38,28 +38,125 @@ impl Diff {
     }
 
     fn diff(v1: &Value, v2: &Value) -> Vec<Change> {
-        let mut d = Recorder::default();
-        treediff::diff(v1, v2, &mut d);
-        let changes = d
-            .calls
-            .iter()
-            .map(|c| match c {
-                ChangeType::Added(k, v) => Change::Added(Diff::friendly_key(k), v.to_string()),
-                ChangeType::Removed(k, v) => Change::Removed(Diff::friendly_key(k), v.to_string()),
-                ChangeType::Unchanged(k, v) => {
-                    Change::Unchanged(Diff::friendly_key(k), v.to_string())
+        let mut changes = vec![];
+        Diff::walk(&mut vec![], v1, v2, &mut changes);
+        changes
+    }
+
+    fn walk(path: &mut Vec<String>, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+        match (old, new) {
+            (Value::Object(o), Value::Object(n)) => {
+                for (k, ov) in o {
+                    path.push(k.to_string());
+                    match n.get(k) {
+                        Some(nv) => Diff::walk(path, ov, nv, changes),
+                        None if ov.is_object() => Diff::walk(path, ov, &Value::Null, changes),
+                        None => {
+                            changes.push(Change::Removed(Diff::friendly_key(path), ov.to_string()))
+                        }
+                    }
+                    path.pop();
                 }
-                ChangeType::Modified(k, old, new) => {
-                    let old = old.to_string();
-                    if old == "null" {
-                        Change::Added(Diff::friendly_key(k), new.to_string())
-                    } else {
-                        Change::Modified(Diff::friendly_key(k), old, new.to_string())
+                for (k, nv) in n.iter().filter(|(k, _)| !o.contains_key(*k)) {
+                    path.push(k.to_string());
+                    match nv {
+                        Value::Object(_) => Diff::walk(path, &Value::Null, nv, changes),
+                        _ => changes.push(Change::Added(Diff::friendly_key(path), nv.to_string())),
                     }
+                    path.pop();
                 }
-            })
-            .collect();
-        changes
+            }
+            (Value::Array(o), Value::Array(n)) => Diff::walk_list(path, o, n, changes),
+            // Optional objects coming or going are shown field by field
+            (Value::Null, Value::Object(_)) => {
+                Diff::walk(path, &Value::Object(Default::default()), new, changes)
+            }
+            (Value::Object(_), Value::Null) => {
+                Diff::walk(path, old, &Value::Object(Default::default()), changes)
+            }
+            _ if old == new => {
+                changes.push(Change::Unchanged(Diff::friendly_key(path), new.to_string()))
+            }
+            _ if old.is_null() => {
+                changes.push(Change::Added(Diff::friendly_key(path), new.to_string()))
+            }
+            _ => changes.push(Change::Modified(
+                Diff::friendly_key(path),
+                old.to_string(),
+                new.to_string(),
+            )),
+        }
+    }
+
+    /// Lists are diffed by their longest common subsequence rather than by index, so inserting
+    /// a step at the top of a job shows up as one added step instead of every step changing.
+    fn walk_list(path: &mut Vec<String>, old: &[Value], new: &[Value], changes: &mut Vec<Change>) {
+        let (n, m) = (old.len(), new.len());
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        let (mut removed, mut added) = (vec![], vec![]);
+        while i < n || j < m {
+            if i < n && j < m && old[i] == new[j] {
+                Diff::flush_list(path, old, new, &mut removed, &mut added, changes);
+                path.push(j.to_string());
+                changes.push(Change::Unchanged(
+                    Diff::friendly_key(path),
+                    new[j].to_string(),
+                ));
+                path.pop();
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                removed.push(i);
+                i += 1;
+            } else {
+                added.push(j);
+                j += 1;
+            }
+        }
+        Diff::flush_list(path, old, new, &mut removed, &mut added, changes);
+    }
+
+    /// Items removed and added at the same spot are reported as modified, the rest as is
+    fn flush_list(
+        path: &mut Vec<String>,
+        old: &[Value],
+        new: &[Value],
+        removed: &mut Vec<usize>,
+        added: &mut Vec<usize>,
+        changes: &mut Vec<Change>,
+    ) {
+        let paired = removed.len().min(added.len());
+        for (&i, &j) in removed.iter().zip(added.iter()) {
+            path.push(j.to_string());
+            Diff::walk(path, &old[i], &new[j], changes);
+            path.pop();
+        }
+        for &i in &removed[paired..] {
+            path.push(i.to_string());
+            changes.push(Change::Removed(
+                Diff::friendly_key(path),
+                old[i].to_string(),
+            ));
+            path.pop();
+        }
+        for &j in &added[paired..] {
+            path.push(j.to_string());
+            changes.push(Change::Added(Diff::friendly_key(path), new[j].to_string()));
+            path.pop();
+        }
+        removed.clear();
+        added.clear();
     }
 
     fn new_diff(v1: &Value, v2: &Value) -> Vec<Change> {
@@ -72,12 +169,48 @@ impl Diff {
             .collect()
     }
 
-    fn friendly_key(keys: &[Key]) -> String {
-        return keys
-            .iter()
-            .map(|f| f.to_string())
-            .collect::<Vec<_>>()
-            .join(".");
+    fn friendly_key(keys: &[String]) -> String {
+        keys.join(".")
+    }
+
+    /// Drops every change at or below any of `paths`
+    pub fn without(self, paths: &[&str]) -> Self {
+        let changes = self
+            .changes
+            .into_iter()
+            .filter(|c| {
+                let key = match c {
+                    Change::Added(k, _)
+                    | Change::Removed(k, _)
+                    | Change::Unchanged(k, _)
+                    | Change::Modified(k, _, _) => k,
+                };
+                !paths.iter().any(|p| matches_path(key, p))
+            })
+            .collect();
+        Self { changes, ..self }
+    }
+
+    /// Renames keys (and values like environment ids) to what's written in dbt_cloud.yml,
+    /// dropping the ones that can't be written there
+    pub fn localize(self, names: &FieldNames) -> Self {
+        let changes = self
+            .changes
+            .into_iter()
+            .filter_map(|c| match c {
+                Change::Added(k, v) => names.key(&k).map(|l| Change::Added(l, names.value(&k, &v))),
+                Change::Removed(k, v) => names
+                    .key(&k)
+                    .map(|l| Change::Removed(l, names.value(&k, &v))),
+                Change::Unchanged(k, v) => names
+                    .key(&k)
+                    .map(|l| Change::Unchanged(l, names.value(&k, &v))),
+                Change::Modified(k, old, new) => names
+                    .key(&k)
+                    .map(|l| Change::Modified(l, names.value(&k, &old), names.value(&k, &new))),
+            })
+            .collect();
+        Self { changes, ..self }
     }
 
     pub fn has_changes(&self) -> bool {
@@ -91,10 +224,6 @@ impl Diff {
         for c in &self.changes {
             match c {
                 Change::Added(k, v) => {
-                    // TODO: Cleanup but for now we only support cron so no need to show this change
-                    if k == "schedule.cron" || k.contains("schedule.time") {
-                        continue;
-                    }
                     println!("{}{} {} {}", padding, "+".green(), k.green(), v.green());
                 }
                 Change::Removed(k, v) => {
@@ -104,10 +233,6 @@ impl Diff {
                     // DO NOTHING
                 }
                 Change::Modified(k, old, new) => {
-                    // TODO: Cleanup but for now we only support cron so no need to show this change
-                    if k == "schedule.cron" || k.contains("schedule.time") {
-                        continue;
-                    }
                     println!(
                         "{}{} {} {} -> {}",
                         padding,
@@ -121,3 +246,60 @@ impl Diff {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Change, Diff};
+
+    fn visible(diff: &Diff) -> Vec<String> {
+        diff.changes
+            .iter()
+            .filter_map(|c| match c {
+                Change::Added(k, v) => Some(format!("+ {} {}", k, v)),
+                Change::Removed(k, v) => Some(format!("- {} {}", k, v)),
+                Change::Modified(k, old, new) => Some(format!("~ {} {} -> {}", k, old, new)),
+                Change::Unchanged(_, _) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inserted_step_is_one_change() {
+        let old = json!({"execute_steps": ["dbt run", "dbt test", "dbt docs generate"]});
+        let new =
+            json!({"execute_steps": ["dbt seed", "dbt run", "dbt test", "dbt docs generate"]});
+        let diff = Diff::from(old, new);
+        assert_eq!(visible(&diff), vec!["+ execute_steps.0 \"dbt seed\""]);
+    }
+
+    #[test]
+    fn removed_and_modified_steps() {
+        let old = json!({"execute_steps": ["dbt seed", "dbt run", "dbt test"]});
+        let new = json!({"execute_steps": ["dbt run --full-refresh", "dbt test"]});
+        let diff = Diff::from(old, new);
+        assert_eq!(
+            visible(&diff),
+            vec![
+                "~ execute_steps.0 \"dbt seed\" -> \"dbt run --full-refresh\"",
+                "- execute_steps.1 \"dbt run\""
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_values() {
+        let old = json!({"settings": {"threads": 4, "target_name": "prod"}, "id": null});
+        let new = json!({"settings": {"threads": 8, "target_name": "prod"}, "id": 1});
+        let diff = Diff::from(old, new);
+        assert_eq!(visible(&diff), vec!["+ id 1", "~ settings.threads 4 -> 8"]);
+        assert!(!Diff::from(json!({"a": [1, 2]}), json!({"a": [1, 2]})).has_changes());
+
+        let added = Diff::from(
+            json!({"run_after": null}),
+            json!({"run_after": {"job_id": 1}}),
+        );
+        assert_eq!(visible(&added), vec!["+ run_after.job_id 1"]);
+    }
+}
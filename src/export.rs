@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+
+use crate::{
+    local::{IgnoredField, Job as LocalJob, RunStatus},
+    lookup::{self, ManagedJob},
+    remote::{Environment as RemoteEnvironment, Job as RemoteJob},
+    workspace::Workspace,
+};
+
+/// Just enough HCL for resources and import blocks, laid out the way `terraform fmt` lays it out
+struct Block {
+    header: String,
+    body: Vec<Line>,
+}
+
+enum Line {
+    Attribute(String, String),
+    /// A map of single line values like `triggers`
+    Object(String, Vec<(String, String)>),
+    Block(Block),
+}
+
+impl Block {
+    fn new(header: &str) -> Self {
+        Block {
+            header: header.to_string(),
+            body: vec![],
+        }
+    }
+
+    fn attribute(mut self, name: &str, value: impl ToString) -> Self {
+        self.body
+            .push(Line::Attribute(name.to_string(), value.to_string()));
+        self
+    }
+
+    fn object(mut self, name: &str, values: Vec<(&str, String)>) -> Self {
+        let values = values
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        self.body.push(Line::Object(name.to_string(), values));
+        self
+    }
+
+    fn block(mut self, block: Block) -> Self {
+        self.body.push(Line::Block(block));
+        self
+    }
+
+    fn render(&self, indent: usize) -> String {
+        let pad = " ".repeat(indent);
+        let mut out = format!("{}{} {{\n", pad, self.header);
+        let inner = " ".repeat(indent + 2);
+        // `=` lines up across attributes next to each other, like `terraform fmt` does it
+        let mut width = 0;
+        for (i, line) in self.body.iter().enumerate() {
+            if i == 0 || !matches!(self.body[i - 1], Line::Attribute(..)) {
+                width = self.body[i..]
+                    .iter()
+                    .map_while(|l| match l {
+                        Line::Attribute(name, _) => Some(name.len()),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0);
+            }
+            match line {
+                Line::Attribute(name, value) => {
+                    out += &format!("{}{:<width$} = {}\n", inner, name, value, width = width);
+                }
+                Line::Object(name, values) => {
+                    out += &format!("{}{} = {{\n", inner, name);
+                    let inner_width = values.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+                    for (k, v) in values {
+                        out += &format!("{}  {:<w$} = {}\n", inner, k, v, w = inner_width);
+                    }
+                    out += &format!("{}}}\n", inner);
+                }
+                Line::Block(block) => {
+                    if i > 0 {
+                        out += "\n";
+                    }
+                    out += &block.render(indent + 2);
+                }
+            }
+        }
+        out + &format!("{}}}\n", pad)
+    }
+}
+
+fn string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace("${", "$${")
+        .replace("%{", "%%{");
+    format!("\"{}\"", escaped)
+}
+
+fn list(values: &[String]) -> String {
+    let values: Vec<_> = values.iter().map(|v| string(v)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+/// Terraform names can't have dots or spaces and can't start with a digit
+fn identifier(value: &str) -> String {
+    let name: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn status_name(status: &RunStatus) -> &'static str {
+    match status {
+        RunStatus::Success => "success",
+        RunStatus::Error => "error",
+        RunStatus::Cancelled => "canceled",
+    }
+}
+
+/// What `ignore_changes` in dbt_cloud.yml becomes in a `lifecycle` block
+fn terraform_paths(field: &IgnoredField) -> &'static [&'static str] {
+    match field {
+        IgnoredField::Environment => &["environment_id"],
+        IgnoredField::Target => &["target_name"],
+        IgnoredField::Timeout => &["timeout_seconds"],
+        IgnoredField::Threads => &["num_threads"],
+        IgnoredField::Ci => &[
+            "triggers[\"github_webhook\"]",
+            "triggers[\"git_provider_webhook\"]",
+            "triggers_on_draft_pr",
+            "run_compare_changes",
+            "compare_changes_flags",
+            "run_lint",
+            "errors_on_lint_failure",
+        ],
+        IgnoredField::Schedule => &["triggers[\"schedule\"]", "schedule_type", "schedule_cron"],
+        IgnoredField::Steps => &["execute_steps"],
+        IgnoredField::GenerateDocs => &["generate_docs"],
+        IgnoredField::DeferToJobId => &["deferring_job_id"],
+        IgnoredField::DeferToEnvId => &["deferring_environment_id"],
+        IgnoredField::DbtVersion => &["dbt_version"],
+        IgnoredField::Description => &["description"],
+        IgnoredField::JobType => &["job_type"],
+        IgnoredField::RunGenerateSources => &["run_generate_sources"],
+    }
+}
+
+/// Names resources after YAML keys so that the output reads like dbt_cloud.yml
+struct Exporter<'a> {
+    workspace: &'a Workspace<'a>,
+    managed: Vec<ManagedJob>,
+    remote_jobs: Vec<RemoteJob>,
+    remote_environments: Vec<RemoteEnvironment>,
+    project_names: HashMap<i64, String>,
+}
+
+impl Exporter<'_> {
+    fn name(&self, key: &str) -> String {
+        match &self.workspace.name {
+            Some(account) => identifier(&format!("{}_{}", account, key)),
+            None => identifier(key),
+        }
+    }
+
+    fn resource(&self, kind: &str, name: &str) -> Block {
+        let block = Block::new(&format!("resource \"{}\" \"{}\"", kind, name));
+        match &self.workspace.name {
+            Some(account) => {
+                block.attribute("provider", format!("dbtcloud.{}", identifier(account)))
+            }
+            None => block,
+        }
+    }
+
+    fn import(&self, to: &str, id: String) -> Block {
+        let block = Block::new("import");
+        let block = match &self.workspace.name {
+            Some(account) => {
+                block.attribute("provider", format!("dbtcloud.{}", identifier(account)))
+            }
+            None => block,
+        };
+        block.attribute("to", to).attribute("id", string(&id))
+    }
+
+    fn project_reference(&self, id: i64) -> String {
+        let yaml = &self.workspace.yaml;
+        let mut keys: Vec<_> = yaml
+            .projects
+            .iter()
+            .filter(|(_, p)| p.id == Some(id))
+            .collect();
+        keys.sort_by_key(|(k, _)| *k);
+        match keys.first() {
+            Some((key, _)) => format!("dbtcloud_project.{}.id", self.name(key)),
+            None => id.to_string(),
+        }
+    }
+
+    fn environment_reference(&self, id: i64) -> String {
+        let yaml = &self.workspace.yaml;
+        let mut keys: Vec<_> = yaml
+            .environments
+            .iter()
+            .filter(|(_, e)| e.id == Some(id))
+            .collect();
+        keys.sort_by_key(|(k, _)| *k);
+        match keys.first() {
+            Some((key, _)) => format!("dbtcloud_environment.{}.environment_id", self.name(key)),
+            None => id.to_string(),
+        }
+    }
+
+    fn job_reference(&self, target: &ManagedJob) -> String {
+        format!(
+            "dbtcloud_job.{}.id",
+            self.name(&format!("{}_{}", target.project_key, target.key))
+        )
+    }
+
+    fn projects(&self) -> Vec<Block> {
+        let mut projects: Vec<_> = self.workspace.yaml.projects.iter().collect();
+        projects.sort_by_key(|(k, _)| *k);
+        let mut blocks = vec![];
+        for (key, project) in projects {
+            let name = self.name(key);
+            let title = project
+                .name
+                .clone()
+                .or_else(|| self.project_names.get(&project.id()).cloned())
+                .unwrap_or_else(|| key.to_string());
+            blocks.push(
+                self.resource("dbtcloud_project", &name)
+                    .attribute("name", string(&title)),
+            );
+            blocks.push(self.import(
+                &format!("dbtcloud_project.{}", name),
+                project.id().to_string(),
+            ));
+        }
+        blocks
+    }
+
+    fn environments(&self) -> Result<Vec<Block>, String> {
+        let yaml = &self.workspace.yaml;
+        let mut environments: Vec<_> = yaml.environments.iter().collect();
+        environments.sort_by_key(|(k, _)| *k);
+        let mut blocks = vec![];
+        for (key, environment) in environments {
+            let remote = self
+                .remote_environments
+                .iter()
+                .find(|e| e.id == Some(environment.id()));
+            // Without dbt Cloud's answer the project comes from `project` or from a job in it
+            let project_id = remote
+                .map(|e| e.project_id)
+                .or_else(|| {
+                    let project = yaml.projects.get(environment.project.as_ref()?)?;
+                    project.id
+                })
+                .or_else(|| {
+                    self.managed
+                        .iter()
+                        .find(|j| j.job.environment_id == environment.id())
+                        .map(|j| j.job.project_id)
+                })
+                .ok_or_else(|| {
+                    format!(
+                        "can't tell which project environment `{}` is in, set its `project`",
+                        key
+                    )
+                })?;
+            let title = environment
+                .name
+                .clone()
+                .or_else(|| remote.map(|e| e.name.clone()))
+                .unwrap_or_else(|| key.to_string());
+            let kind = remote
+                .and_then(|e| e.type_field.clone())
+                .unwrap_or_else(|| "deployment".to_string());
+
+            let name = self.name(key);
+            let mut block = self
+                .resource("dbtcloud_environment", &name)
+                .attribute("project_id", self.project_reference(project_id))
+                .attribute("name", string(&title))
+                .attribute("type", string(&kind));
+            if let Some(deployment_type) = remote.and_then(|e| e.deployment_type.as_ref()) {
+                block = block.attribute("deployment_type", string(deployment_type));
+            }
+            if let Some(version) = remote.and_then(|e| e.dbt_version.as_ref()) {
+                block = block.attribute("dbt_version", string(version));
+            }
+            blocks.push(block);
+            blocks.push(self.import(
+                &format!("dbtcloud_environment.{}", name),
+                format!("{}:{}", project_id, environment.id()),
+            ));
+        }
+        Ok(blocks)
+    }
+
+    fn jobs(&self) -> Result<Vec<Block>, String> {
+        let yaml = &self.workspace.yaml;
+        let mut managed: Vec<_> = self.managed.iter().collect();
+        managed.sort_by_key(|j| (&j.project_key, &j.key));
+        let mut blocks = vec![];
+        for managed_job in managed {
+            let project = &yaml.projects[&managed_job.project_key];
+            let local: &LocalJob = &project.jobs[&managed_job.key];
+            let job = &managed_job.job;
+            let context =
+                |err: String| format!("{}.{}: {}", managed_job.project_key, managed_job.key, err);
+
+            let name = self.name(&format!("{}_{}", managed_job.project_key, managed_job.key));
+            let mut block = self
+                .resource("dbtcloud_job", &name)
+                .attribute("project_id", self.project_reference(job.project_id))
+                .attribute(
+                    "environment_id",
+                    self.environment_reference(job.environment_id),
+                )
+                .attribute("name", string(&job.name));
+            if !job.description.is_empty() {
+                block = block.attribute("description", string(&job.description));
+            }
+            block = block
+                .attribute("job_type", string(&job.job_type))
+                .attribute("execute_steps", list(&job.execute_steps))
+                .attribute("target_name", string(&job.settings.target_name))
+                .attribute("num_threads", job.settings.threads);
+            if let Some(version) = &job.dbt_version {
+                block = block.attribute("dbt_version", string(version));
+            }
+            if job.execution.timeout_seconds > 0 {
+                block = block.attribute("timeout_seconds", job.execution.timeout_seconds);
+            }
+            block = block
+                .attribute("generate_docs", job.generate_docs)
+                .attribute("run_generate_sources", job.run_generate_sources);
+
+            if let Some(reference) = &local.defer_to_job {
+                let target =
+                    lookup::find_defer_to_job(&self.managed, &managed_job.project_key, reference)
+                        .map_err(context)?;
+                block = block.attribute("deferring_job_id", self.job_reference(target));
+            } else if let Some(id) = job.deferring_job_definition_id {
+                block = block.attribute("deferring_job_id", id);
+            }
+            if let Some(id) = job.deferring_environment_id {
+                block = block.attribute("deferring_environment_id", self.environment_reference(id));
+            }
+
+            block = block.object(
+                "triggers",
+                vec![
+                    ("github_webhook", job.triggers.github_webhook.to_string()),
+                    (
+                        "git_provider_webhook",
+                        job.triggers.git_provider_webhook.to_string(),
+                    ),
+                    ("schedule", job.triggers.schedule.to_string()),
+                    ("on_merge", (job.job_type == "merge").to_string()),
+                ],
+            );
+            if job.triggers.schedule {
+                block = block
+                    .attribute("schedule_type", string("custom_cron"))
+                    .attribute("schedule_cron", string(&job.schedule.cron));
+            }
+            if job.job_type == "ci"
+                || job.triggers.github_webhook
+                || job.triggers.git_provider_webhook
+            {
+                block = block
+                    .attribute("triggers_on_draft_pr", job.triggers_on_draft_pr)
+                    .attribute("run_compare_changes", job.run_compare_changes)
+                    .attribute("compare_changes_flags", string(&job.compare_changes_flags))
+                    .attribute("run_lint", job.run_lint)
+                    .attribute("errors_on_lint_failure", job.errors_on_lint_failure);
+            }
+
+            // Like `plan`, a disabled job doesn't run after anything
+            if let Some(run_after) = local
+                .run_after
+                .as_ref()
+                .filter(|_| local.enabled != Some(false))
+            {
+                let target =
+                    lookup::find_run_after(&self.managed, &managed_job.project_key, run_after)
+                        .map_err(context)?;
+                let statuses: Vec<_> = run_after
+                    .statuses
+                    .as_deref()
+                    .unwrap_or(&[RunStatus::Success])
+                    .iter()
+                    .map(|s| status_name(s).to_string())
+                    .collect();
+                block = block.block(
+                    Block::new("job_completion_trigger_condition")
+                        .attribute("job_id", self.job_reference(target))
+                        .attribute("project_id", self.project_reference(target.job.project_id))
+                        .attribute("statuses", list(&statuses)),
+                );
+            }
+
+            let mut ignored = local.ignored_fields(project);
+            if local.enabled == Some(false) {
+                ignored.retain(|f| !matches!(f, IgnoredField::Ci | IgnoredField::Schedule));
+            }
+            ignored.sort();
+            ignored.dedup();
+            if !ignored.is_empty() {
+                let paths: Vec<_> = ignored.iter().flat_map(terraform_paths).copied().collect();
+                block = block.block(
+                    Block::new("lifecycle")
+                        .attribute("ignore_changes", format!("[{}]", paths.join(", "))),
+                );
+            }
+            blocks.push(block);
+
+            let remote = self
+                .remote_jobs
+                .iter()
+                .find(|r| r.project_id == job.project_id && r.name == job.name);
+            // Jobs dbterra hasn't created yet have nothing to import
+            if let Some(id) = remote.and_then(|r| r.id) {
+                blocks.push(self.import(&format!("dbtcloud_job.{}", name), id.to_string()));
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+pub const HEADER: &str = "# Generated by `dbterra export terraform` from dbt_cloud.yml
+
+terraform {
+  required_providers {
+    dbtcloud = {
+      source = \"dbt-labs/dbtcloud\"
+    }
+  }
+}
+";
+
+/// Projects, environments and jobs of one account as `dbtcloud` resources, each with an
+/// `import` block for what already exists in dbt Cloud
+pub fn terraform(workspace: &Workspace) -> Result<String, String> {
+    let client = workspace.client;
+    let remote_jobs = client
+        .get_jobs()
+        .map_err(|err| format!("failed to get jobs: {}", err))?
+        .data
+        .unwrap_or_default();
+    let remote_environments = client
+        .get_environments()
+        .map_err(|err| format!("failed to get environments: {}", err))?
+        .data
+        .unwrap_or_default();
+    // Only asked for when dbt_cloud.yml leaves a name out
+    let project_names = if workspace.yaml.projects.values().any(|p| p.name.is_none()) {
+        client
+            .get_projects()
+            .map_err(|err| format!("failed to get projects: {}", err))?
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|p| Some((p.id?, p.name)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let exporter = Exporter {
+        workspace,
+        managed: lookup::managed_jobs(&workspace.yaml, workspace.config),
+        remote_jobs,
+        remote_environments,
+        project_names,
+    };
+
+    // The token comes from DBT_CLOUD_TOKEN, like it does for dbterra
+    let provider = Block::new("provider \"dbtcloud\"");
+    let provider = match &workspace.name {
+        Some(account) => provider.attribute("alias", string(&identifier(account))),
+        None => provider,
+    };
+    let host_url = format!("{}/api", workspace.config.base_url.trim_end_matches('/'));
+    let mut blocks = vec![provider
+        .attribute("account_id", workspace.config.account_id)
+        .attribute("host_url", string(&host_url))];
+    blocks.extend(exporter.projects());
+    blocks.extend(exporter.environments()?);
+    blocks.extend(exporter.jobs()?);
+    let rendered: Vec<_> = blocks.iter().map(|b| b.render(0)).collect();
+    Ok(rendered.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::terraform;
+    use crate::{
+        local::Root,
+        remote::{DbtCloudApi, Environment, FakeDbtCloud, Job},
+        testing::workspace,
+    };
+
+    #[test]
+    fn resources_and_imports() {
+        let yaml: Root = serde_yaml::from_str(
+            "
+projects:
+  example:
+    id: 456
+    name: Example
+    ignore_changes: [threads]
+    jobs:
+      seed:
+        environment: prod
+        target: production
+        steps: [dbt seed]
+        schedule:
+          cron: \"0 9 * * *\"
+      full_run:
+        environment: prod
+        target: production
+        timeout: 600
+        steps: [\"dbt build --vars '{\\\"day\\\": \\\"${DAY}\\\"}'\"]
+        run_after:
+          job: seed
+          statuses: [success, error]
+environments:
+  prod:
+    id: 789
+",
+        )
+        .unwrap();
+        let fake = FakeDbtCloud::new(123);
+        fake.add_environment(Environment {
+            id: Some(789),
+            account_id: 123,
+            project_id: 456,
+            name: "Production".to_string(),
+            dbt_version: Some("versionless".to_string()),
+            type_field: Some("deployment".to_string()),
+            deployment_type: Some("production".to_string()),
+        });
+        fake.create_job(&Job {
+            account_id: 123,
+            project_id: 456,
+            name: "Seed".to_string(),
+            ..Job::default()
+        })
+        .unwrap();
+        let workspace = workspace(yaml, &fake);
+
+        let exported = terraform(&workspace).unwrap();
+        assert_eq!(exported, terraform(&workspace).unwrap());
+        assert_eq!(
+            exported,
+            r#"provider "dbtcloud" {
+  account_id = 123
+  host_url   = "https://cloud.getdbt.com/api"
+}
+
+resource "dbtcloud_project" "example" {
+  name = "Example"
+}
+
+import {
+  to = dbtcloud_project.example
+  id = "456"
+}
+
+resource "dbtcloud_environment" "prod" {
+  project_id      = dbtcloud_project.example.id
+  name            = "Production"
+  type            = "deployment"
+  deployment_type = "production"
+  dbt_version     = "versionless"
+}
+
+import {
+  to = dbtcloud_environment.prod
+  id = "456:789"
+}
+
+resource "dbtcloud_job" "example_full_run" {
+  project_id           = dbtcloud_project.example.id
+  environment_id       = dbtcloud_environment.prod.environment_id
+  name                 = "Full Run"
+  job_type             = "other"
+  execute_steps        = ["dbt build --vars '{\"day\": \"$${DAY}\"}'"]
+  target_name          = "production"
+  num_threads          = 4
+  timeout_seconds      = 600
+  generate_docs        = false
+  run_generate_sources = false
+  triggers = {
+    github_webhook       = false
+    git_provider_webhook = false
+    schedule             = false
+    on_merge             = false
+  }
+
+  job_completion_trigger_condition {
+    job_id     = dbtcloud_job.example_seed.id
+    project_id = dbtcloud_project.example.id
+    statuses   = ["success", "error"]
+  }
+
+  lifecycle {
+    ignore_changes = [num_threads]
+  }
+}
+
+resource "dbtcloud_job" "example_seed" {
+  project_id           = dbtcloud_project.example.id
+  environment_id       = dbtcloud_environment.prod.environment_id
+  name                 = "Seed"
+  job_type             = "scheduled"
+  execute_steps        = ["dbt seed"]
+  target_name          = "production"
+  num_threads          = 4
+  generate_docs        = false
+  run_generate_sources = false
+  triggers = {
+    github_webhook       = false
+    git_provider_webhook = false
+    schedule             = true
+    on_merge             = false
+  }
+  schedule_type = "custom_cron"
+  schedule_cron = "0 9 * * *"
+
+  lifecycle {
+    ignore_changes = [num_threads]
+  }
+}
+
+import {
+  to = dbtcloud_job.example_seed
+  id = "1"
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use crate::local::Environment;
+use crate::remote::Run;
+
+/// `remote::Job` paths and the `local::Job` names people actually edit in dbt_cloud.yml
+const LOCAL_NAMES: [(&str, &str); 17] = [
+    ("settings.threads", "threads"),
+    ("settings.target_name", "target"),
+    ("triggers.github_webhook", "ci.run_on_pr"),
+    ("triggers.custom_branch_only", "ci.custom_branch_only"),
+    ("triggers_on_draft_pr", "ci.run_on_draft_pr"),
+    ("run_compare_changes", "ci.compare_changes"),
+    ("compare_changes_flags", "ci.compare_changes_flags"),
+    ("run_lint", "ci.lint"),
+    ("errors_on_lint_failure", "ci.errors_on_lint_failure"),
+    ("triggers.schedule", "schedule"),
+    ("deferring_job_definition_id", "defer_to_job_id"),
+    ("deferring_environment_id", "defer_to_env_id"),
+    ("execution.timeout_seconds", "timeout"),
+    ("execute_steps", "steps"),
+    ("environment_id", "environment"),
+    ("job_completion_trigger_condition.condition", "run_after"),
+    ("job_completion_trigger_condition", "run_after"),
+];
+
+const RUN_AFTER_JOB_ID: &str = "job_completion_trigger_condition.condition.job_id";
+const RUN_AFTER_STATUSES: &str = "job_completion_trigger_condition.condition.statuses";
+
+/// Remote only details that can't be expressed in dbt_cloud.yml
+const HIDDEN: [&str; 2] = ["schedule.cron", "schedule.time"];
+
+/// Translates diff keys (and some values) from the dbt Cloud API to the dbt_cloud.yml vocabulary
+#[derive(Debug, Clone, Default)]
+pub struct FieldNames {
+    environments: HashMap<i64, String>,
+}
+
+impl FieldNames {
+    pub fn new(environments: &HashMap<String, Environment>) -> Self {
+        Self {
+            environments: environments
+                .iter()
+                .map(|(k, e)| (e.id(), k.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Returns `None` for keys that shouldn't be shown at all
+    pub fn key(&self, key: &str) -> Option<String> {
+        if HIDDEN.iter().any(|h| matches_path(key, h)) {
+            return None;
+        }
+        if let Some(rest) = key.strip_prefix("schedule.date.") {
+            return Some(format!("schedule.{}", rest));
+        }
+        let renamed = LOCAL_NAMES.iter().find_map(|(remote, local)| {
+            matches_path(key, remote).then(|| format!("{}{}", local, &key[remote.len()..]))
+        });
+        Some(renamed.unwrap_or_else(|| key.to_string()))
+    }
+
+    /// Environments are shown by their YAML key when we know it, run statuses by name
+    pub fn value(&self, key: &str, value: &str) -> String {
+        if key == "environment_id" {
+            if let Some(name) = value.parse().ok().and_then(|id| self.environments.get(&id)) {
+                return name.to_string();
+            }
+        }
+        if key == RUN_AFTER_JOB_ID && value == "null" {
+            return "(known after apply)".to_string();
+        }
+        if matches_path(key, RUN_AFTER_STATUSES) {
+            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(value) {
+                return status_names(parsed).to_string();
+            }
+        }
+        value.to_string()
+    }
+}
+
+fn status_names(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(statuses) => statuses.into_iter().map(status_names).collect(),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(Run::SUCCESS) => "success".into(),
+            Some(Run::ERROR) => "error".into(),
+            Some(Run::CANCELLED) => "cancelled".into(),
+            _ => n.into(),
+        },
+        other => other,
+    }
+}
+
+/// True when `key` is `path` or something nested within it
+pub fn matches_path(key: &str, path: &str) -> bool {
+    key == path || key.starts_with(&format!("{}.", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::FieldNames;
+    use crate::local::Environment;
+
+    #[test]
+    fn local_vocabulary() {
+        let mut environments = HashMap::new();
+        environments.insert(
+            "bizops".to_string(),
+            Environment {
+                id: Some(1),
+                ..Environment::default()
+            },
+        );
+        environments.insert(
+            "staging".to_string(),
+            Environment {
+                id: Some(2),
+                ..Environment::default()
+            },
+        );
+        let names = FieldNames::new(&environments);
+
+        assert_eq!(names.key("settings.threads").unwrap(), "threads");
+        assert_eq!(
+            names.key("triggers.github_webhook").unwrap(),
+            "ci.run_on_pr"
+        );
+        assert_eq!(names.key("execute_steps.2").unwrap(), "steps.2");
+        assert_eq!(names.key("execution.timeout_seconds").unwrap(), "timeout");
+        assert_eq!(names.key("schedule.date.cron").unwrap(), "schedule.cron");
+        assert_eq!(names.key("name").unwrap(), "name");
+        assert_eq!(names.key("schedule.cron"), None);
+        assert_eq!(names.key("schedule.time.interval"), None);
+
+        assert_eq!(names.value("environment_id", "2"), "staging");
+        assert_eq!(names.value("environment_id", "3"), "3");
+        assert_eq!(names.value("project_id", "2"), "2");
+
+        assert_eq!(
+            names
+                .key("job_completion_trigger_condition.condition.job_id")
+                .unwrap(),
+            "run_after.job_id"
+        );
+        assert_eq!(
+            names.value(
+                "job_completion_trigger_condition.condition.statuses",
+                "[10,30]"
+            ),
+            "[\"success\",\"cancelled\"]"
+        );
+        assert_eq!(
+            names.value("job_completion_trigger_condition.condition.job_id", "null"),
+            "(known after apply)"
+        );
+    }
+}
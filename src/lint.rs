@@ -0,0 +1,268 @@
+use colored::Colorize;
+
+use crate::{
+    local::{Job as LocalJob, Lint, LintRule, Severity},
+    lookup::managed_jobs,
+    remote::Job as RemoteJob,
+    workspace::Workspace,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rule {
+    Timeout,
+    MaxThreads,
+    FullRefresh,
+    CiStateModified,
+    PreferBuild,
+}
+
+const RULES: [Rule; 5] = [
+    Rule::Timeout,
+    Rule::MaxThreads,
+    Rule::FullRefresh,
+    Rule::CiStateModified,
+    Rule::PreferBuild,
+];
+
+impl Rule {
+    fn name(&self) -> &'static str {
+        match self {
+            Rule::Timeout => "timeout",
+            Rule::MaxThreads => "max_threads",
+            Rule::FullRefresh => "full_refresh",
+            Rule::CiStateModified => "ci_state_modified",
+            Rule::PreferBuild => "prefer_build",
+        }
+    }
+
+    fn config<'a>(&self, lint: &'a Lint) -> Option<&'a LintRule> {
+        match self {
+            Rule::Timeout => lint.timeout.as_ref(),
+            Rule::MaxThreads => lint.max_threads.as_ref(),
+            Rule::FullRefresh => lint.full_refresh.as_ref(),
+            Rule::CiStateModified => lint.ci_state_modified.as_ref(),
+            Rule::PreferBuild => lint.prefer_build.as_ref(),
+        }
+    }
+
+    /// `max_threads` does nothing until it's told the limits, so it can afford to be strict
+    fn default_severity(&self) -> Severity {
+        match self {
+            Rule::MaxThreads => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+
+    /// What's wrong with the job, if anything
+    fn check(
+        &self,
+        job: &RemoteJob,
+        local: &LocalJob,
+        config: Option<&LintRule>,
+    ) -> Option<String> {
+        let steps = &job.execute_steps;
+        match self {
+            Rule::Timeout => (job.triggers.schedule && job.execution.timeout_seconds == 0)
+                .then(|| "is scheduled but has no timeout".to_string()),
+            Rule::MaxThreads => {
+                let max = config?.options()?.max.as_ref()?.get(&local.environment)?;
+                (job.settings.threads > *max).then(|| {
+                    format!(
+                        "uses {} threads but `{}` allows at most {}",
+                        job.settings.threads, local.environment, max
+                    )
+                })
+            }
+            Rule::FullRefresh => {
+                let full_refresh = steps.iter().any(|s| s.contains("--full-refresh"));
+                (job.triggers.schedule && full_refresh)
+                    .then(|| "runs --full-refresh on a schedule".to_string())
+            }
+            Rule::CiStateModified => {
+                let ci = job.triggers.github_webhook
+                    || job.triggers.git_provider_webhook
+                    || job.job_type == "ci";
+                let defers = job.deferring_job_definition_id.is_some()
+                    || job.deferring_environment_id.is_some()
+                    || local.defer_to_job.is_some()
+                    || steps.iter().any(|s| s.contains("--defer"));
+                if !ci {
+                    None
+                } else if !steps.iter().any(|s| s.contains("state:modified")) {
+                    Some("runs on pull requests without selecting state:modified".to_string())
+                } else if !defers {
+                    Some("runs on pull requests without deferring".to_string())
+                } else {
+                    None
+                }
+            }
+            Rule::PreferBuild => {
+                let run = steps.iter().position(|s| is_command(s, "run"))?;
+                steps[run..].iter().any(|s| is_command(s, "test")).then(|| {
+                    "runs `dbt run` and then `dbt test`, `dbt build` does both".to_string()
+                })
+            }
+        }
+    }
+}
+
+fn is_command(step: &str, command: &str) -> bool {
+    let mut words = step.split_whitespace();
+    words.next() == Some("dbt") && words.next() == Some(command)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub rule: &'static str,
+    /// `project.key`, prefixed with the account when dbt_cloud.yml manages several
+    pub job: String,
+    pub message: String,
+}
+
+/// Checks every job in dbt_cloud.yml as it would be sent to dbt Cloud, no requests are made
+pub fn check(workspace: &Workspace) -> Vec<Finding> {
+    let lint = workspace.yaml.lint.clone().unwrap_or_default();
+    let mut findings = vec![];
+    for managed in managed_jobs(&workspace.yaml, workspace.config) {
+        let local = &workspace.yaml.projects[&managed.project_key].jobs[&managed.key];
+        for rule in RULES {
+            let config = rule.config(&lint);
+            let severity = config
+                .and_then(|c| c.severity())
+                .unwrap_or(rule.default_severity());
+            let environments = config
+                .and_then(|c| c.options())
+                .and_then(|o| o.environments.as_ref());
+            if severity == Severity::Off
+                || environments.is_some_and(|e| !e.contains(&local.environment))
+            {
+                continue;
+            }
+            if let Some(message) = rule.check(&managed.job, local, config) {
+                findings.push(Finding {
+                    severity,
+                    rule: rule.name(),
+                    job: workspace.prefix(&format!("{}.{}", managed.project_key, managed.key)),
+                    message,
+                });
+            }
+        }
+    }
+    findings
+}
+
+pub fn has_errors(findings: &[Finding]) -> bool {
+    findings.iter().any(|f| f.severity == Severity::Error)
+}
+
+pub fn pretty_print(findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("no problems found");
+        return;
+    }
+    for f in findings {
+        let severity = match f.severity {
+            Severity::Error => "error".red(),
+            _ => "warning".yellow(),
+        };
+        println!("{:<7} {}: {} [{}]", severity, f.job, f.message, f.rule);
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    println!(
+        "\n{} error(s), {} warning(s)",
+        errors,
+        findings.len() - errors
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::{
+        local::{Root, Severity},
+        remote::FakeDbtCloud,
+        testing::workspace,
+    };
+
+    #[test]
+    fn rules() {
+        let yaml: Root = serde_yaml::from_str(
+            "
+lint:
+  timeout: error
+  max_threads:
+    max: {prod: 8}
+  prefer_build: off
+  ci_state_modified:
+    environments: [ci]
+projects:
+  example:
+    id: 456
+    jobs:
+      nightly:
+        environment: prod
+        target: production
+        threads: 16
+        schedule:
+          cron: \"0 2 * * *\"
+        steps:
+          - dbt run --full-refresh
+          - dbt test
+      pr:
+        environment: ci
+        target: ci
+        timeout: 600
+        ci:
+          run_on_pr: true
+        steps:
+          - dbt build --select state:modified+
+      other_pr:
+        environment: prod
+        target: ci
+        ci:
+          run_on_pr: true
+        steps:
+          - dbt build
+environments:
+  prod:
+    id: 789
+  ci:
+    id: 790
+",
+        )
+        .unwrap();
+        let fake = FakeDbtCloud::new(123);
+        let workspace = workspace(yaml, &fake);
+
+        let mut found: Vec<_> = check(&workspace)
+            .into_iter()
+            .map(|f| (f.job, f.rule, f.severity))
+            .collect();
+        found.sort_by_key(|(job, rule, _)| (job.clone(), *rule));
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "example.nightly".to_string(),
+                    "full_refresh",
+                    Severity::Warning
+                ),
+                (
+                    "example.nightly".to_string(),
+                    "max_threads",
+                    Severity::Error
+                ),
+                ("example.nightly".to_string(), "timeout", Severity::Error),
+                (
+                    "example.pr".to_string(),
+                    "ci_state_modified",
+                    Severity::Warning
+                ),
+            ]
+        );
+    }
+}
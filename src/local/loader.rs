@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::Root;
+
+pub const FILE_NAME: &str = "dbt_cloud.yml";
+
+/// Looks for dbt_cloud.yml in `start` and then each of its parents, like git does for `.git`
+pub fn find(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads and merges every file, `-` being stdin. Projects, environments and accounts can
+/// only be defined once across all of them.
+pub fn load(paths: &[PathBuf]) -> Result<Root, String> {
+    let mut merged = Root::default();
+    let mut sources: HashMap<String, String> = HashMap::new();
+    for path in paths {
+        let source = match path.to_str() {
+            Some("-") => "stdin".to_string(),
+            _ => path.display().to_string(),
+        };
+        let root = read(path).map_err(|err| format!("{}: {}", source, err))?;
+        merge(&mut merged, root, &source, &mut sources)?;
+    }
+    Ok(merged)
+}
+
+fn read(path: &Path) -> Result<Root, String> {
+    let contents = if path.to_str() == Some("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|err| err.to_string())?;
+        contents
+    } else {
+        std::fs::read_to_string(path).map_err(|err| err.to_string())?
+    };
+    serde_yaml::from_str(&contents).map_err(|err| err.to_string())
+}
+
+fn merge(
+    into: &mut Root,
+    root: Root,
+    source: &str,
+    sources: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    if let Some(account) = root.account {
+        match &into.account {
+            Some(existing) if existing.id != account.id => {
+                return Err(format!(
+                    "{} sets account {} but {} sets account {}",
+                    source, account.id, sources["account"], existing.id
+                ))
+            }
+            _ => {
+                sources
+                    .entry("account".to_string())
+                    .or_insert(source.to_string());
+                into.account = Some(account);
+            }
+        }
+    }
+    insert_all(
+        &mut into.projects,
+        root.projects,
+        "project",
+        source,
+        sources,
+    )?;
+    insert_all(
+        &mut into.environments,
+        root.environments,
+        "environment",
+        source,
+        sources,
+    )?;
+    if let Some(lint) = root.lint {
+        if let Some(first) = sources.get("lint") {
+            return Err(format!("lint is set in both {} and {}", first, source));
+        }
+        sources.insert("lint".to_string(), source.to_string());
+        into.lint = Some(lint);
+    }
+    if let Some(accounts) = root.accounts {
+        insert_all(
+            into.accounts.get_or_insert_with(HashMap::new),
+            accounts,
+            "account",
+            source,
+            sources,
+        )?;
+    }
+    Ok(())
+}
+
+fn insert_all<T>(
+    into: &mut HashMap<String, T>,
+    values: HashMap<String, T>,
+    kind: &str,
+    source: &str,
+    sources: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (key, value) in values {
+        let qualified = format!("{} `{}`", kind, key);
+        if let Some(first) = sources.get(&qualified) {
+            return Err(format!(
+                "{} is defined in both {} and {}",
+                qualified, first, source
+            ));
+        }
+        sources.insert(qualified, source.to_string());
+        into.insert(key, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{find, load, FILE_NAME};
+
+    #[test]
+    fn finds_and_merges_files() {
+        let dir = std::env::temp_dir().join(format!("dbterra-loader-{}", std::process::id()));
+        let nested = dir.join("models").join("staging");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            dir.join(FILE_NAME),
+            "
+account:
+  id: 123
+projects:
+  example:
+    id: 456
+    jobs: {}
+environments:
+  prod:
+    id: 789
+",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("marketing.yml"),
+            "
+account:
+  id: 123
+projects:
+  marketing:
+    id: 457
+    jobs: {}
+",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("duplicate.yml"),
+            "
+projects:
+  example:
+    id: 999
+    jobs: {}
+",
+        )
+        .unwrap();
+
+        let found = find(&nested).unwrap();
+        assert_eq!(found, dir.join(FILE_NAME));
+
+        let root = load(&[found.clone(), dir.join("marketing.yml")]).unwrap();
+        assert_eq!(root.account.unwrap().id, 123);
+        assert_eq!(root.projects.len(), 2);
+        assert_eq!(root.environments["prod"].id, Some(789));
+
+        let err = load(&[found, dir.join("duplicate.yml")]).unwrap_err();
+        assert!(err.starts_with("project `example` is defined in both"));
+        assert!(load(&[dir.join("missing.yml")]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
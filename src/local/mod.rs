@@ -1,3 +1,4 @@
+pub mod loader;
 mod types;
 
 pub use types::*;
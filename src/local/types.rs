@@ -5,8 +5,14 @@ use serde::{Deserialize, Serialize};
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Root {
     pub account: Option<Account>,
+    #[serde(default)]
     pub projects: HashMap<String, Project>,
+    #[serde(default)]
     pub environments: HashMap<String, Environment>,
+    /// Several accounts managed from one file, used instead of `account`/`projects`/`environments`
+    pub accounts: Option<HashMap<String, AccountEntry>>,
+    /// Applies to every account
+    pub lint: Option<Lint>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -14,15 +20,96 @@ pub struct Account {
     pub id: i64,
 }
 
+/// An account under `accounts:`, its settings win over the `DBT_CLOUD_*` environment variables
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountEntry {
+    pub id: Option<i64>,
+    pub base_url: Option<String>,
+    /// Named profile holding the credentials for this account
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub projects: HashMap<String, Project>,
+    #[serde(default)]
+    pub environments: HashMap<String, Environment>,
+}
+
+/// One account's part of dbt_cloud.yml, see `Root::split_accounts`
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct AccountRoot {
+    /// `None` when dbt_cloud.yml doesn't use `accounts:`
+    pub name: Option<String>,
+    pub id: Option<i64>,
+    pub base_url: Option<String>,
+    pub profile: Option<String>,
+    pub yaml: Root,
+}
+
+impl Root {
+    /// Splits the file into one `Root` per account, sorted by name
+    pub fn split_accounts(self) -> Result<Vec<AccountRoot>, String> {
+        let accounts = match self.accounts {
+            None => {
+                return Ok(vec![AccountRoot {
+                    yaml: self,
+                    ..AccountRoot::default()
+                }])
+            }
+            Some(accounts) => accounts,
+        };
+        if self.account.is_some() || !self.projects.is_empty() || !self.environments.is_empty() {
+            return Err(
+                "`accounts` can't be combined with a top level `account`, `projects` or `environments`"
+                    .to_string(),
+            );
+        }
+        let mut split: Vec<_> = accounts
+            .into_iter()
+            .map(|(name, entry)| AccountRoot {
+                name: Some(name),
+                id: entry.id,
+                base_url: entry.base_url,
+                profile: entry.profile,
+                yaml: Root {
+                    account: entry.id.map(|id| Account { id }),
+                    projects: entry.projects,
+                    environments: entry.environments,
+                    accounts: None,
+                    lint: self.lint.clone(),
+                },
+            })
+            .collect();
+        split.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(split)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Project {
-    pub id: i64,
+    /// Looked up by `name` when left out
+    pub id: Option<i64>,
+    pub name: Option<String>,
     pub jobs: HashMap<String, Job>,
+    /// Applies to every job in the project on top of the job's own `ignore_changes`
+    pub ignore_changes: Option<Vec<IgnoredField>>,
+    /// Default for jobs that don't have their own `notifications`
+    pub notifications: Option<Notifications>,
+    /// What happens to jobs removed from dbt_cloud.yml, defaults to `delete`
+    pub on_remove: Option<OnRemove>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnRemove {
+    Delete,
+    /// Keeps the job and its run history but turns off all of its triggers
+    Deactivate,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Job {
     pub name: Option<String>,
+    /// `false` keeps the job but turns off all of its triggers, defaults to `true`
+    pub enabled: Option<bool>,
     pub environment: String,
     pub target: String,
     pub timeout: Option<i64>,
@@ -33,12 +120,208 @@ pub struct Job {
     pub generate_docs: Option<bool>,
     pub defer_to_job_id: Option<i64>,
     pub defer_to_env_id: Option<i64>,
+    /// Key of the job to defer to, `project.key` for one in another project
+    pub defer_to_job: Option<String>,
+    /// Key of the environment to defer to
+    pub defer_to_environment: Option<String>,
+    /// Defaults to `ci` for jobs that run on pull requests, `scheduled` for ones on a schedule and
+    /// `other` for the rest
+    pub job_type: Option<JobType>,
+    /// Defaults to empty
+    pub description: Option<String>,
+    /// Runs `dbt source freshness` before the steps, defaults to `false`
+    pub run_generate_sources: Option<bool>,
+    /// A version like `1.7.0-latest` or a release track like `versionless`, defaults to the environment's
+    pub dbt_version: Option<String>,
+    pub ignore_changes: Option<Vec<IgnoredField>>,
+    pub notifications: Option<Notifications>,
+    pub run_after: Option<RunAfter>,
+}
+
+impl Job {
+    /// The job's own ignored fields together with the project's
+    pub fn ignored_fields(&self, project: &Project) -> Vec<IgnoredField> {
+        let mut fields: Vec<_> = self
+            .ignore_changes
+            .iter()
+            .chain(project.ignore_changes.iter())
+            .flatten()
+            .copied()
+            .collect();
+        fields.sort();
+        fields.dedup();
+        fields
+    }
+
+    /// The job's own notifications, or else the project's. `None` leaves them unmanaged.
+    pub fn notifications<'a>(&'a self, project: &'a Project) -> Option<&'a Notifications> {
+        self.notifications
+            .as_ref()
+            .or(project.notifications.as_ref())
+    }
+}
+
+/// Fields whose remote value is kept as is, e.g. because they are tweaked in the UI on purpose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IgnoredField {
+    Environment,
+    Target,
+    Timeout,
+    Threads,
+    Ci,
+    Schedule,
+    Steps,
+    GenerateDocs,
+    DeferToJobId,
+    DeferToEnvId,
+    DbtVersion,
+    Description,
+    JobType,
+    RunGenerateSources,
+}
+
+/// Rules checked by `dbterra lint`, each one can be turned off or given another severity
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lint {
+    /// Scheduled jobs set a `timeout`, a warning by default
+    pub timeout: Option<LintRule>,
+    /// Jobs use at most `max` threads for their environment, an error by default
+    pub max_threads: Option<LintRule>,
+    /// Scheduled jobs don't `--full-refresh`, a warning by default
+    pub full_refresh: Option<LintRule>,
+    /// CI jobs select `state:modified` and defer, a warning by default
+    pub ci_state_modified: Option<LintRule>,
+    /// `dbt run` followed by `dbt test` is a `dbt build`, a warning by default
+    pub prefer_build: Option<LintRule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Off,
+    Warning,
+    Error,
+}
+
+/// Just a severity, or a severity with options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LintRule {
+    Severity(Severity),
+    Options(LintOptions),
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LintOptions {
+    pub severity: Option<Severity>,
+    /// Environment keys the rule is limited to, all of them by default
+    pub environments: Option<Vec<String>>,
+    /// Most threads allowed by environment key, only for `max_threads`
+    pub max: Option<HashMap<String, i64>>,
+}
+
+impl LintRule {
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            LintRule::Severity(severity) => Some(*severity),
+            LintRule::Options(options) => options.severity,
+        }
+    }
+
+    pub fn options(&self) -> Option<&LintOptions> {
+        match self {
+            LintRule::Severity(_) => None,
+            LintRule::Options(options) => Some(options),
+        }
+    }
+}
+
+/// Runs a job when another one finishes, instead of lining up their schedules
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunAfter {
+    /// YAML key or name of the other job
+    pub job: String,
+    /// YAML key of the other job's project, defaults to this job's project
+    pub project: Option<String>,
+    /// Defaults to `[success]`
+    pub statuses: Option<Vec<RunStatus>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Success,
+    Error,
+    Cancelled,
+}
+
+/// Who to tell when a job finishes, for each way it can finish
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notifications {
+    #[serde(default)]
+    pub on_success: Recipients,
+    #[serde(default)]
+    pub on_failure: Recipients,
+    #[serde(default)]
+    pub on_cancel: Recipients,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recipients {
+    #[serde(default)]
+    pub emails: Vec<String>,
+    #[serde(default)]
+    pub slack: Vec<SlackChannel>,
+}
+
+/// A channel id like `C0123456789`, optionally with its name for display in dbt Cloud
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SlackChannel {
+    Id(String),
+    Channel { id: String, name: Option<String> },
+}
+
+impl SlackChannel {
+    pub fn id(&self) -> &str {
+        match self {
+            SlackChannel::Id(id) | SlackChannel::Channel { id, .. } => id,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            SlackChannel::Id(_) => None,
+            SlackChannel::Channel { name, .. } => name.as_deref(),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CI {
     pub run_on_pr: Option<bool>,
     pub custom_branch_only: Option<bool>,
+    /// Also run for draft pull requests, defaults to `false`
+    pub run_on_draft_pr: Option<bool>,
+    /// Compare the pull request's changes against production, defaults to `false`
+    pub compare_changes: Option<bool>,
+    /// Defaults to `--select state:modified`
+    pub compare_changes_flags: Option<String>,
+    /// Lint the SQL that changed, defaults to `false`
+    pub lint: Option<bool>,
+    /// Fail the run when linting finds something, defaults to `true`
+    pub errors_on_lint_failure: Option<bool>,
+}
+
+/// What dbt Cloud uses the job for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobType {
+    Ci,
+    Merge,
+    Scheduled,
+    Other,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -48,5 +331,82 @@ pub struct Schedule {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Environment {
-    pub id: i64,
+    /// Looked up by `name` when left out
+    pub id: Option<i64>,
+    pub name: Option<String>,
+    /// Key of the project the environment belongs to, for names used in several projects
+    pub project: Option<String>,
+}
+
+impl Project {
+    pub fn id(&self) -> i64 {
+        self.id
+            .expect("project ids are resolved when loading dbt_cloud.yml")
+    }
+}
+
+impl Environment {
+    pub fn id(&self) -> i64 {
+        self.id
+            .expect("environment ids are resolved when loading dbt_cloud.yml")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Root;
+
+    #[test]
+    fn split_accounts() {
+        let legacy: Root = serde_yaml::from_str(
+            "
+account:
+  id: 123
+projects:
+  example:
+    id: 456
+    jobs: {}
+",
+        )
+        .unwrap();
+        let split = legacy.clone().split_accounts().unwrap();
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].name, None);
+        assert_eq!(split[0].yaml, legacy);
+
+        let multi: Root = serde_yaml::from_str(
+            "
+accounts:
+  us:
+    id: 123
+    profile: us
+    projects:
+      example:
+        id: 456
+        jobs: {}
+  eu:
+    id: 789
+    base_url: https://emea.dbt.com
+",
+        )
+        .unwrap();
+        let split = multi.split_accounts().unwrap();
+        let names: Vec<_> = split.iter().map(|a| a.name.as_deref().unwrap()).collect();
+        assert_eq!(names, vec!["eu", "us"]);
+        assert_eq!(split[0].base_url.as_deref(), Some("https://emea.dbt.com"));
+        assert_eq!(split[1].yaml.account.as_ref().unwrap().id, 123);
+        assert_eq!(split[1].yaml.projects["example"].id, Some(456));
+
+        let mixed: Root = serde_yaml::from_str(
+            "
+account:
+  id: 123
+accounts:
+  us:
+    id: 123
+",
+        )
+        .unwrap();
+        assert!(mixed.split_accounts().is_err());
+    }
 }
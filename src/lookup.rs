@@ -0,0 +1,198 @@
+use std::error::Error;
+
+use crate::{
+    config::Config,
+    local::{Root, RunAfter},
+    remote::{DbtCloudApi, Job as RemoteJob},
+    workspace::Workspace,
+};
+
+/// A job declared in dbt_cloud.yml, converted the same way `plan` converts it
+pub struct ManagedJob {
+    pub project_key: String,
+    pub key: String,
+    pub job: RemoteJob,
+}
+
+impl ManagedJob {
+    /// How plans refer to jobs that may not have an id yet
+    pub fn id_key(&self) -> (i64, String) {
+        (self.job.project_id, self.job.name.to_string())
+    }
+}
+
+pub fn managed_jobs(yaml: &Root, config: &Config) -> Vec<ManagedJob> {
+    let mut jobs: Vec<_> = yaml
+        .projects
+        .iter()
+        .flat_map(|(project_key, project)| {
+            let local_config = config.with_project_id(project.id());
+            project.jobs.iter().map(move |(key, job)| ManagedJob {
+                project_key: project_key.to_string(),
+                key: key.to_string(),
+                job: RemoteJob::from_local_job(key, job.clone(), &local_config, &yaml.environments),
+            })
+        })
+        .collect();
+    jobs.sort_by(|a, b| (&a.project_key, &a.job.name).cmp(&(&b.project_key, &b.job.name)));
+    jobs
+}
+
+/// Finds the job a `run_after` points at, `project_key` being the project of the job it's on
+pub fn find_run_after<'a>(
+    managed: &'a [ManagedJob],
+    project_key: &str,
+    run_after: &RunAfter,
+) -> Result<&'a ManagedJob, String> {
+    let project = run_after.project.as_deref().unwrap_or(project_key);
+    find_reference(managed, "run_after", project, &run_after.job)
+}
+
+/// Finds the job a `defer_to_job` of `key` or `project.key` points at
+pub fn find_defer_to_job<'a>(
+    managed: &'a [ManagedJob],
+    project_key: &str,
+    reference: &str,
+) -> Result<&'a ManagedJob, String> {
+    let (project, job) = reference
+        .split_once('.')
+        .unwrap_or((project_key, reference));
+    find_reference(managed, "defer_to_job", project, job)
+}
+
+fn find_reference<'a>(
+    managed: &'a [ManagedJob],
+    field: &str,
+    project: &str,
+    job: &str,
+) -> Result<&'a ManagedJob, String> {
+    managed
+        .iter()
+        .find(|j| j.project_key == project && (j.key == job || j.job.name == job))
+        .ok_or_else(|| {
+            format!(
+                "{} refers to `{}` but there's no such job in project `{}`",
+                field, job, project
+            )
+        })
+}
+
+/// Finds a managed job by its YAML key or its name, optionally limited to one account or project
+pub fn find_managed_job<'a, 'w>(
+    workspaces: &'w [Workspace<'a>],
+    query: &str,
+    account: Option<&str>,
+    project: Option<&str>,
+) -> Result<(&'w Workspace<'a>, ManagedJob), Box<dyn Error>> {
+    let mut matches: Vec<_> = workspaces
+        .iter()
+        .filter(|w| account.is_none_or(|a| w.name.as_deref() == Some(a)))
+        .flat_map(|w| {
+            managed_jobs(&w.yaml, w.config)
+                .into_iter()
+                .map(move |j| (w, j))
+        })
+        .filter(|(_, j)| project.is_none_or(|p| p == j.project_key))
+        .filter(|(_, j)| j.key == query || j.job.name == query)
+        .collect();
+    match matches.len() {
+        0 => Err(format!("no job named `{}` in dbt_cloud.yml", query).into()),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            let projects: Vec<_> = matches
+                .iter()
+                .map(|(w, j)| w.prefix(&j.project_key))
+                .collect();
+            Err(format!(
+                "`{}` matches jobs in more than one project ({}), pick one with `--project` or `--account`",
+                query,
+                projects.join(", ")
+            )
+            .into())
+        }
+    }
+}
+
+/// Finds the deployed counterpart of a managed job, matched by name like `plan` does
+pub fn find_remote_job(
+    api: &dyn DbtCloudApi,
+    managed: &ManagedJob,
+) -> Result<RemoteJob, Box<dyn Error>> {
+    api.get_jobs_for_project(managed.job.project_id)?
+        .data
+        .unwrap_or_default()
+        .into_iter()
+        .find(|j| j.name == managed.job.name)
+        .ok_or_else(|| {
+            format!(
+                "job \"{}\" hasn't been created in dbt Cloud yet, run `dbterra apply` first",
+                managed.job.name
+            )
+            .into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_managed_job;
+    use crate::{local::Root, remote::FakeDbtCloud, testing::workspace, workspace::Workspace};
+
+    fn root() -> Root {
+        serde_yaml::from_str(
+            "
+projects:
+  analytics:
+    id: 1
+    jobs:
+      seed:
+        environment: prod
+        target: production
+        steps: [dbt seed]
+      full_run:
+        name: Full Production Run
+        environment: prod
+        target: production
+        steps: [dbt run]
+  marketing:
+    id: 2
+    jobs:
+      seed:
+        environment: prod
+        target: production
+        steps: [dbt seed]
+environments:
+  prod:
+    id: 3
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn by_key_or_name() {
+        let fake = FakeDbtCloud::new(123);
+        let workspaces = [workspace(root(), &fake)];
+        let (_, by_key) = find_managed_job(&workspaces, "full_run", None, None).unwrap();
+        let (_, by_name) =
+            find_managed_job(&workspaces, "Full Production Run", None, None).unwrap();
+        assert_eq!(by_key.job, by_name.job);
+        assert_eq!(by_key.job.project_id, 1);
+    }
+
+    #[test]
+    fn ambiguous_needs_project_or_account() {
+        let fake = FakeDbtCloud::new(123);
+        let account = |name: &str| Workspace {
+            name: Some(name.to_string()),
+            ..workspace(root(), &fake)
+        };
+        let workspaces = [account("eu"), account("us")];
+        assert!(find_managed_job(&workspaces[..1], "seed", None, None).is_err());
+        assert!(find_managed_job(&workspaces, "seed", None, Some("marketing")).is_err());
+        let (workspace, seed) =
+            find_managed_job(&workspaces, "Seed", Some("us"), Some("marketing")).unwrap();
+        assert_eq!(workspace.name.as_deref(), Some("us"));
+        assert_eq!(seed.job.project_id, 2);
+        assert!(find_managed_job(&workspaces, "missing", None, None).is_err());
+    }
+}
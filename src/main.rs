@@ -1,21 +1,46 @@
 use colored::Colorize;
 use dialoguer::Confirm;
 use plan::Plan;
-use std::error::Error;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
 
-use crate::local::Root;
+use crate::local::AccountRoot;
 use crate::remote::Job as RemoteJob;
-use crate::{config::Config, remote::DbtCloudClient};
+use crate::remote::{DbtCloudApi, FakeDbtCloud, MockServer, TriggerRun};
+use crate::schedule::ScheduledJob;
+use crate::{
+    config::{Config, Overrides},
+    remote::DbtCloudClient,
+    workspace::Workspace,
+};
 
+mod artifacts;
+mod capacity;
 mod config;
 mod diff;
+mod export;
+mod fields;
+mod lint;
 mod local;
+mod lookup;
+mod notifications;
 mod plan;
+mod profiles;
 mod remote;
+mod resolve;
+mod run;
+mod schedule;
+mod status;
+#[cfg(test)]
+mod testing;
 mod utils;
+mod versions;
+mod workspace;
 
-use clap::{Parser, Subcommand};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None, arg_required_else_help = true)]
@@ -24,11 +49,55 @@ struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: u8,
 
+    /// Where jobs are read from and applied to: `dbt-cloud` or `fake:<snapshot.json>` to rehearse locally
+    #[arg(long, global = true, default_value = "dbt-cloud", value_parser = parse_backend)]
+    backend: Backend,
+
+    /// dbt_cloud.yml files to read, `-` for stdin, may be repeated [default: the nearest dbt_cloud.yml in this or a parent directory]
+    #[arg(
+        short,
+        long = "config",
+        value_name = "PATH",
+        global = true,
+        env = "DBTERRA_CONFIG"
+    )]
+    configs: Vec<PathBuf>,
+
+    /// Named profile from ~/.config/dbterra/profiles.yml [default: DBTERRA_PROFILE unless the account sets `profile`]
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Overrides the account id from the profile, environment or dbt_cloud.yml
+    #[arg(long, global = true)]
+    account_id: Option<i64>,
+
+    /// Overrides the token from the profile or environment
+    #[arg(long, global = true)]
+    token: Option<String>,
+
+    /// Overrides the base url from the profile or environment
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+
     /// Commands
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+#[derive(Debug, Clone)]
+enum Backend {
+    DbtCloud,
+    Fake(PathBuf),
+}
+
+fn parse_backend(value: &str) -> Result<Backend, String> {
+    match value.split_once(':') {
+        None if value == "dbt-cloud" => Ok(Backend::DbtCloud),
+        Some(("fake", path)) if !path.is_empty() => Ok(Backend::Fake(PathBuf::from(path))),
+        _ => Err("expected `dbt-cloud` or `fake:<snapshot.json>`".to_string()),
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Plans the changes derived from your dbt_cloud.yml file
@@ -37,6 +106,131 @@ enum Commands {
     Apply {
         #[arg(short, long, default_value_t = false)]
         auto_approve: bool,
+        /// Apply even when `dbterra lint` finds errors
+        #[arg(long, default_value_t = false)]
+        allow_lint_errors: bool,
+    },
+    /// Checks the jobs in your dbt_cloud.yml file against the rules under `lint:`
+    Lint,
+    /// Triggers a run of a job and waits for it to finish, exiting with its result
+    Run {
+        /// The job's YAML key or name
+        job: String,
+        /// Only look for the job in this account (key under `accounts:`)
+        #[arg(long)]
+        account: Option<String>,
+        /// Only look for the job in this project (YAML key)
+        #[arg(long)]
+        project: Option<String>,
+        #[arg(long, default_value = "Triggered by dbterra")]
+        cause: String,
+        #[arg(long)]
+        git_branch: Option<String>,
+        /// Run these steps instead of the job's, may be repeated
+        #[arg(long = "step")]
+        steps: Vec<String>,
+        /// Don't wait for the run to finish
+        #[arg(long, default_value_t = false)]
+        no_wait: bool,
+        /// Seconds between status checks
+        #[arg(long, default_value_t = 10)]
+        poll_interval: u64,
+    },
+    /// Lists the jobs from your dbt_cloud.yml file with their most recent runs
+    Status {
+        /// How many recent runs to show per job
+        #[arg(short = 'n', long, default_value_t = 3)]
+        runs: usize,
+        /// Print JSON instead of a table
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Downloads artifacts like manifest.json from a job's latest successful run
+    Artifacts {
+        /// The job's YAML key or name
+        job: String,
+        /// Only look for the job in this account (key under `accounts:`)
+        #[arg(long)]
+        account: Option<String>,
+        /// Only look for the job in this project (YAML key)
+        #[arg(long)]
+        project: Option<String>,
+        /// Download from this run instead of the latest successful one
+        #[arg(long)]
+        run: Option<i64>,
+        /// Directory to download into
+        #[arg(short, long, default_value = ".")]
+        out: PathBuf,
+        /// Artifacts to download, may be repeated [default: manifest.json, run_results.json, catalog.json]
+        #[arg(short, long = "artifact")]
+        artifacts: Vec<String>,
+    },
+    /// Lists when the jobs in your dbt_cloud.yml file run, without asking dbt Cloud
+    #[command(args_conflicts_with_subcommands = true)]
+    Schedule {
+        #[command(subcommand)]
+        command: Option<ScheduleCommands>,
+        /// How far ahead to look, like `90m`, `24h` or `7d`
+        #[arg(long, default_value = "24h", value_parser = schedule::parse_length, conflicts_with = "to")]
+        next: TimeDelta,
+        /// Start of the window in UTC, like `2024-05-01 09:00` [default: now]
+        #[arg(long, value_parser = schedule::parse_time)]
+        from: Option<NaiveDateTime>,
+        /// End of the window in UTC, instead of `--next`
+        #[arg(long, value_parser = schedule::parse_time)]
+        to: Option<NaiveDateTime>,
+        /// Count the runs in each hour instead of listing them
+        #[arg(long, default_value_t = false)]
+        histogram: bool,
+    },
+    /// Simulates a day of scheduled runs against the account's run slots, using recent run times
+    Capacity {
+        /// The day to simulate, in UTC [default: today]
+        #[arg(long)]
+        day: Option<NaiveDate>,
+        /// Read run times and run slots from a file saved with `--save` instead of dbt Cloud
+        #[arg(long, value_name = "PATH", conflicts_with = "save")]
+        durations: Option<PathBuf>,
+        /// Save the run times and run slots fetched from dbt Cloud to a file
+        #[arg(long, value_name = "PATH")]
+        save: Option<PathBuf>,
+        /// How many recent runs of each job to time
+        #[arg(short = 'n', long, default_value_t = 10)]
+        runs: usize,
+        /// Simulate with this many run slots instead of the account's
+        #[arg(long)]
+        run_slots: Option<i64>,
+    },
+    /// Writes the projects, environments and jobs in your dbt_cloud.yml file out for other tools
+    Export {
+        #[command(subcommand)]
+        command: ExportCommands,
+    },
+    /// Serves a local stand-in for the dbt Cloud jobs API, for testing.
+    /// Serves `--account-id` (default 1) and only accepts `--token` when it's given.
+    #[command(hide = true)]
+    MockServer {
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+        /// Load jobs from and save them to this JSON file
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommands {
+    /// Scheduled runs per day and per week, by project and environment
+    Report,
+}
+
+#[derive(Subcommand)]
+enum ExportCommands {
+    /// `dbtcloud` provider resources with `import` blocks for what already exists
+    Terraform {
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        out: Option<PathBuf>,
     },
 }
 
@@ -50,34 +244,190 @@ fn main() {
         _ => println!("Don't be crazy"),
     }
 
-    // TODO: Move into function and call from correct commands
-    let parse_yaml = read_yaml_file("./dbt_cloud.yml");
+    if let Some(Commands::MockServer { port, snapshot }) = &cli.command {
+        let account_id = cli.account_id.unwrap_or(1);
+        serve_mock(*port, account_id, cli.token.clone(), snapshot.clone());
+        return;
+    }
+
+    let parse_yaml = config_paths(&cli.configs).and_then(|paths| local::loader::load(&paths));
     if parse_yaml.is_err() {
         println!("{}", "failed to read dbt_cloud.yml file:".red());
         println!("  {}", parse_yaml.err().unwrap());
         exit(1);
     }
 
-    let yaml = parse_yaml.unwrap();
-    let config = Config::build(&yaml).expect("failed to build config");
-    let client = DbtCloudClient::new(&config);
+    let accounts = parse_yaml.unwrap().split_accounts().unwrap_or_else(|err| {
+        println!("{}", "failed to read dbt_cloud.yml file:".red());
+        println!("  {}", err);
+        exit(1);
+    });
+    if accounts.len() > 1
+        && (cli.account_id.is_some() || cli.token.is_some() || cli.profile.is_some())
+    {
+        println!(
+            "{}",
+            "--account-id, --token and --profile would apply to every account, set `id` and `profile` under `accounts:` instead".red()
+        );
+        exit(1);
+    }
+    // Only needs dbt_cloud.yml, so no token either
+    if let Some(Commands::Schedule {
+        command,
+        next,
+        from,
+        to,
+        histogram,
+    }) = &cli.command
+    {
+        let jobs = scheduled_jobs(&accounts);
+        match command {
+            Some(ScheduleCommands::Report) => schedule::print_report(&schedule::report(&jobs)),
+            None => {
+                let from = from.unwrap_or_else(|| Utc::now().naive_utc());
+                let Some(to) = to.or_else(|| from.checked_add_signed(*next)) else {
+                    let message = "--next is too long, it ends past the last supported date";
+                    Cli::command()
+                        .error(ErrorKind::ValueValidation, message)
+                        .exit()
+                };
+                let runs = schedule::upcoming(&jobs, from, to);
+                if *histogram {
+                    schedule::print_histogram(&schedule::histogram(&runs, from, to));
+                } else if runs.is_empty() {
+                    println!(
+                        "no scheduled runs between {} and {}",
+                        from.format(schedule::TIME_FORMAT),
+                        to.format(schedule::TIME_FORMAT)
+                    );
+                } else {
+                    schedule::print_upcoming(&runs);
+                }
+            }
+        }
+        return;
+    }
+    // Taken before the accounts turn into workspaces
+    let scheduled = match &cli.command {
+        Some(Commands::Capacity { .. }) => scheduled_jobs(&accounts),
+        _ => vec![],
+    };
+    if let Some(Commands::Capacity {
+        durations: Some(path),
+        day,
+        run_slots,
+        ..
+    }) = &cli.command
+    {
+        let durations = capacity::Durations::load(path).unwrap_or_else(|err| {
+            println!("{}", err.red());
+            exit(1);
+        });
+        simulate_capacity(&scheduled, &durations, *day, *run_slots);
+        return;
+    }
+    let configs: Vec<Config> = accounts
+        .iter()
+        .map(|account| {
+            // Flags still win, but an account's own settings beat the environment
+            let overrides = Overrides {
+                profile: cli
+                    .profile
+                    .clone()
+                    .or(account.profile.clone())
+                    .or_else(|| std::env::var(profiles::PROFILE_ENV).ok()),
+                account_id: cli.account_id.or(account.id),
+                // The fake backend doesn't check tokens, so there's no need to ask for one
+                token: cli.token.clone().or_else(|| match cli.backend {
+                    Backend::Fake(_) => Some(String::new()),
+                    Backend::DbtCloud => None,
+                }),
+                base_url: cli.base_url.clone().or(account.base_url.clone()),
+            };
+            Config::build(&account.yaml, &overrides).unwrap_or_else(|err| {
+                match &account.name {
+                    Some(name) => println!(
+                        "{}",
+                        format!("failed to build config for account {}:", name).red()
+                    ),
+                    None => println!("{}", "failed to build config:".red()),
+                }
+                println!("  {}", err);
+                exit(1);
+            })
+        })
+        .collect();
+    let fake = match &cli.backend {
+        Backend::DbtCloud => None,
+        // Every account gets its own view of the snapshot below, without accounts there's none
+        Backend::Fake(path) => configs.first().map(|config| {
+            FakeDbtCloud::from_snapshot(path.clone(), config.account_id)
+                .expect("failed to read fake backend snapshot")
+        }),
+    };
+    let clients: Vec<Box<dyn DbtCloudApi>> = configs
+        .iter()
+        .map(|config| -> Box<dyn DbtCloudApi> {
+            match &fake {
+                None => Box::new(DbtCloudClient::new(config)),
+                Some(fake) => Box::new(fake.for_account(config.account_id)),
+            }
+        })
+        .collect();
+    let workspaces: Vec<Workspace> = accounts
+        .into_iter()
+        .zip(configs.iter().zip(clients.iter()))
+        .map(|(account, (config, client))| {
+            let mut yaml = account.yaml;
+            let resolved = resolve::ids(&mut yaml, client.as_ref()).unwrap_or_else(|err| {
+                println!("{}", "failed to look up ids:".red());
+                println!("  {}", err);
+                exit(1);
+            });
+            Workspace {
+                name: account.name,
+                yaml,
+                config,
+                client: client.as_ref(),
+                resolved,
+            }
+        })
+        .collect();
 
     match &cli.command {
         Some(Commands::Plan) => {
-            let plan = Plan::from(yaml, &client, &config);
-            plan.pretty_print();
+            for plan in plans(&workspaces) {
+                plan.pretty_print();
+            }
             println!("\nno changes applied. to apply changes, run `dbterra apply`");
         }
-        Some(Commands::Apply { auto_approve }) => {
-            let plan = Plan::from(yaml, &client, &config);
-            if !plan.has_changes() {
+        Some(Commands::Apply {
+            auto_approve,
+            allow_lint_errors,
+        }) => {
+            let findings: Vec<_> = workspaces.iter().flat_map(lint::check).collect();
+            if !findings.is_empty() {
+                lint::pretty_print(&findings);
+                println!();
+            }
+            if lint::has_errors(&findings) && !allow_lint_errors {
+                println!(
+                    "{}",
+                    "lint errors block apply, fix them or pass --allow-lint-errors".red()
+                );
+                exit(1);
+            }
+            let plans = plans(&workspaces);
+            if !plans.iter().any(|p| p.has_changes()) {
                 println!(
                     "{}",
                     "no changes detected for any project, exiting...".red()
                 );
                 exit(0);
             }
-            plan.pretty_print();
+            for plan in &plans {
+                plan.pretty_print();
+            }
             println!();
             let mut prompt = Confirm::new();
             let prompt = prompt
@@ -85,15 +435,229 @@ fn main() {
                 .wait_for_newline(true);
             if *auto_approve || prompt.interact().unwrap() {
                 println!("applying changes...");
-                plan.apply(&client);
+                for (plan, workspace) in plans.iter().zip(&workspaces) {
+                    plan.apply(workspace.client);
+                }
+            }
+        }
+        Some(Commands::Lint) => {
+            let findings: Vec<_> = workspaces.iter().flat_map(lint::check).collect();
+            lint::pretty_print(&findings);
+            if lint::has_errors(&findings) {
+                exit(1);
+            }
+        }
+        Some(Commands::Run {
+            job,
+            account,
+            project,
+            cause,
+            git_branch,
+            steps,
+            no_wait,
+            poll_interval,
+        }) => {
+            let (workspace, remote) =
+                find_job(&workspaces, job, account.as_deref(), project.as_deref());
+            let client = workspace.client;
+            let trigger = TriggerRun {
+                cause: cause.to_string(),
+                git_branch: git_branch.clone(),
+                steps_override: if steps.is_empty() {
+                    None
+                } else {
+                    Some(steps.clone())
+                },
+            };
+            let job_id = remote.id.expect("remote jobs always have an id");
+            let triggered = run::trigger(client, job_id, &trigger).expect("failed to trigger run");
+            if *no_wait {
+                exit(0);
+            }
+            let finished = run::wait(client, triggered.id, Duration::from_secs(*poll_interval))
+                .expect("failed to check run status");
+            run::print_result(&finished);
+            exit(if finished.is_success() { 0 } else { 1 });
+        }
+        Some(Commands::Status { runs, json }) => {
+            let mut statuses = vec![];
+            for workspace in &workspaces {
+                statuses.extend(status::collect(workspace, *runs).expect("failed to get job runs"));
+            }
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&statuses).unwrap());
+            } else {
+                status::pretty_print(&statuses);
+            }
+        }
+        Some(Commands::Artifacts {
+            job,
+            account,
+            project,
+            run,
+            out,
+            artifacts,
+        }) => {
+            let (workspace, remote) =
+                find_job(&workspaces, job, account.as_deref(), project.as_deref());
+            let client = workspace.client;
+            let job_id = remote.id.expect("remote jobs always have an id");
+            let found = artifacts::find_run(client, job_id, *run).unwrap_or_else(|err| {
+                println!("{}", err.to_string().red());
+                exit(1);
+            });
+            let artifacts = if artifacts.is_empty() {
+                artifacts::DEFAULT_ARTIFACTS.map(String::from).to_vec()
+            } else {
+                artifacts.clone()
+            };
+            println!("downloading artifacts from run {}", found.id);
+            let written = artifacts::download(client, &found, &artifacts, out)
+                .expect("failed to download artifacts");
+            if written.is_empty() {
+                exit(1);
+            }
+        }
+        Some(Commands::Capacity {
+            day,
+            save,
+            runs,
+            run_slots,
+            ..
+        }) => {
+            let mut durations = capacity::Durations::default();
+            for workspace in &workspaces {
+                durations
+                    .extend(capacity::collect(workspace, *runs).expect("failed to get job runs"));
+            }
+            if let Some(path) = save {
+                durations.save(path).unwrap_or_else(|err| {
+                    println!("{}", err.red());
+                    exit(1);
+                });
+            }
+            simulate_capacity(&scheduled, &durations, *day, *run_slots);
+        }
+        Some(Commands::Export {
+            command: ExportCommands::Terraform { out },
+        }) => {
+            let mut rendered = vec![export::HEADER.to_string()];
+            for workspace in &workspaces {
+                rendered.push(export::terraform(workspace).unwrap_or_else(|err| {
+                    println!("{}", "failed to export:".red());
+                    println!("  {}", err);
+                    exit(1);
+                }));
+            }
+            let rendered = rendered.join("\n");
+            match out {
+                Some(path) => std::fs::write(path, rendered).unwrap_or_else(|err| {
+                    println!(
+                        "{}",
+                        format!("failed to write {}: {}", path.display(), err).red()
+                    );
+                    exit(1);
+                }),
+                None => print!("{}", rendered),
             }
         }
-        None => {}
+        Some(Commands::MockServer { .. }) | Some(Commands::Schedule { .. }) | None => {}
+    }
+}
+
+fn scheduled_jobs(accounts: &[AccountRoot]) -> Vec<ScheduledJob> {
+    schedule::scheduled_jobs(accounts).unwrap_or_else(|err| {
+        println!("{}", "failed to read schedules:".red());
+        println!("  {}", err);
+        exit(1);
+    })
+}
+
+/// One simulation per account, each against its own run slots
+fn simulate_capacity(
+    jobs: &[ScheduledJob],
+    durations: &capacity::Durations,
+    day: Option<NaiveDate>,
+    run_slots: Option<i64>,
+) {
+    let day = day.unwrap_or_else(|| Utc::now().date_naive());
+    let accounts: BTreeSet<_> = jobs.iter().map(|j| j.account.clone()).collect();
+    for account in accounts {
+        let key = account.as_deref().unwrap_or(capacity::DEFAULT_ACCOUNT);
+        let slots = run_slots.or(durations.run_slots.get(key).copied());
+        let Some(slots) = slots else {
+            println!(
+                "{}",
+                format!("no run slots for account `{}`, pass --run-slots", key).red()
+            );
+            exit(1);
+        };
+        let account_jobs: Vec<_> = jobs
+            .iter()
+            .filter(|j| j.account == account)
+            .cloned()
+            .collect();
+        let simulation = capacity::simulate(account, &account_jobs, durations, day, slots);
+        capacity::pretty_print(&simulation);
     }
 }
 
-fn read_yaml_file(filename: &str) -> Result<Root, Box<dyn Error>> {
-    let f = std::fs::File::open(filename)?;
-    let d = serde_yaml::from_reader(f)?;
-    Ok(d)
+fn plans(workspaces: &[Workspace]) -> Vec<Plan> {
+    workspaces
+        .iter()
+        .map(|w| {
+            let plan = Plan::from(w.yaml.clone(), w.client, w.config).unwrap_or_else(|err| {
+                println!("{}", "failed to plan:".red());
+                println!("  {}", err);
+                exit(1);
+            });
+            plan.for_account(w.name.clone())
+                .with_resolved(w.resolved.clone())
+        })
+        .collect()
+}
+
+/// Finds a job from dbt_cloud.yml and its deployed counterpart, exiting when there isn't one
+fn find_job<'w, 'a>(
+    workspaces: &'w [Workspace<'a>],
+    job: &str,
+    account: Option<&str>,
+    project: Option<&str>,
+) -> (&'w Workspace<'a>, RemoteJob) {
+    lookup::find_managed_job(workspaces, job, account, project)
+        .and_then(|(workspace, managed)| {
+            lookup::find_remote_job(workspace.client, &managed).map(|remote| (workspace, remote))
+        })
+        .unwrap_or_else(|err| {
+            println!("{}", err.to_string().red());
+            exit(1);
+        })
+}
+
+fn serve_mock(port: u16, account_id: i64, token: Option<String>, snapshot: Option<PathBuf>) {
+    let api = match snapshot {
+        Some(path) => FakeDbtCloud::from_snapshot(path, account_id)
+            .expect("failed to read mock server snapshot"),
+        None => FakeDbtCloud::new(account_id),
+    };
+    let server = MockServer::bind(&format!("127.0.0.1:{}", port), account_id, token, api)
+        .expect("failed to start mock server");
+    println!("serving account {} at {}", account_id, server.base_url());
+    server.serve();
+}
+
+/// The files given with `--config`, or the nearest dbt_cloud.yml
+fn config_paths(configs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    if !configs.is_empty() {
+        return Ok(configs.to_vec());
+    }
+    let cwd = std::env::current_dir().map_err(|err| err.to_string())?;
+    let path = local::loader::find(&cwd).ok_or_else(|| {
+        format!(
+            "couldn't find {} in {} or any parent directory",
+            local::loader::FILE_NAME,
+            cwd.display()
+        )
+    })?;
+    Ok(vec![path])
 }
@@ -0,0 +1,321 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::{
+    local::{Notifications, Recipients},
+    remote::{DbtCloudApi, Notification},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Event {
+    Success,
+    Failure,
+    Cancel,
+}
+
+const EVENTS: [Event; 3] = [Event::Success, Event::Failure, Event::Cancel];
+
+impl Event {
+    fn key(&self) -> &'static str {
+        match self {
+            Event::Success => "on_success",
+            Event::Failure => "on_failure",
+            Event::Cancel => "on_cancel",
+        }
+    }
+
+    fn job_ids<'a>(&self, notification: &'a Notification) -> &'a Vec<i64> {
+        match self {
+            Event::Success => &notification.on_success,
+            Event::Failure => &notification.on_failure,
+            Event::Cancel => &notification.on_cancel,
+        }
+    }
+
+    fn job_ids_mut<'a>(&self, notification: &'a mut Notification) -> &'a mut Vec<i64> {
+        match self {
+            Event::Success => &mut notification.on_success,
+            Event::Failure => &mut notification.on_failure,
+            Event::Cancel => &mut notification.on_cancel,
+        }
+    }
+}
+
+/// Recipients are matched by email address or Slack channel id, never by their dbt Cloud id
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Recipient {
+    Email(String),
+    Slack(String),
+}
+
+impl Recipient {
+    /// `None` for notifications dbterra doesn't manage, like ones sent to dbt Cloud users
+    fn of(notification: &Notification) -> Option<Recipient> {
+        match notification.notification_type {
+            Notification::EXTERNAL_EMAIL => {
+                notification.external_email.clone().map(Recipient::Email)
+            }
+            Notification::SLACK => notification.slack_channel_id.clone().map(Recipient::Slack),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recipient::Email(email) => write!(f, "{}", email),
+            Recipient::Slack(id) => write!(f, "slack:{}", id),
+        }
+    }
+}
+
+/// Which recipients hear about which events for a single job
+pub type Subscriptions = BTreeSet<(Event, Recipient)>;
+
+pub fn from_local(notifications: &Notifications) -> Subscriptions {
+    let recipients = |r: &Recipients| -> Vec<Recipient> {
+        let emails = r.emails.iter().cloned().map(Recipient::Email);
+        let slack = r.slack.iter().map(|c| Recipient::Slack(c.id().to_string()));
+        emails.chain(slack).collect()
+    };
+    [
+        (Event::Success, &notifications.on_success),
+        (Event::Failure, &notifications.on_failure),
+        (Event::Cancel, &notifications.on_cancel),
+    ]
+    .into_iter()
+    .flat_map(|(event, r)| recipients(r).into_iter().map(move |r| (event, r)))
+    .collect()
+}
+
+/// Slack channel names by id, dbt Cloud shows them next to the notification
+pub fn slack_names(notifications: &Notifications) -> HashMap<String, String> {
+    [
+        &notifications.on_success,
+        &notifications.on_failure,
+        &notifications.on_cancel,
+    ]
+    .into_iter()
+    .flat_map(|r| r.slack.iter())
+    .filter_map(|c| c.name().map(|name| (c.id().to_string(), name.to_string())))
+    .collect()
+}
+
+/// What dbt Cloud currently sends about one job
+pub fn for_job(job_id: i64, notifications: &[Notification]) -> Subscriptions {
+    notifications
+        .iter()
+        .filter_map(|n| Recipient::of(n).map(|r| (n, r)))
+        .flat_map(|(n, r)| {
+            EVENTS
+                .into_iter()
+                .filter(|e| e.job_ids(n).contains(&job_id))
+                .map(move |e| (e, r.clone()))
+        })
+        .collect()
+}
+
+/// Shapes subscriptions like dbt_cloud.yml so they diff the same way as job fields
+pub fn to_value(subscriptions: &Subscriptions) -> Value {
+    let mut events = serde_json::Map::new();
+    for event in EVENTS {
+        let recipients: Vec<_> = subscriptions
+            .iter()
+            .filter(|(e, _)| *e == event)
+            .map(|(_, r)| r.to_string())
+            .collect();
+        events.insert(event.key().to_string(), json!(recipients));
+    }
+    json!({ "notifications": events })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotificationChange {
+    Create(Notification),
+    Update(Notification),
+    Delete(Notification),
+}
+
+/// Works out the notification changes that make dbt Cloud match `desired`, keyed by job id.
+/// Jobs missing from `desired` are left alone, even when they share a recipient with one that isn't.
+pub fn reconcile(
+    account_id: i64,
+    remote: &[Notification],
+    desired: &HashMap<i64, Subscriptions>,
+    slack_names: &HashMap<String, String>,
+) -> Vec<NotificationChange> {
+    let wanted = |event: Event, recipient: &Recipient| -> Vec<i64> {
+        let mut ids: Vec<_> = desired
+            .iter()
+            .filter(|(_, s)| s.contains(&(event, recipient.clone())))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    let mut changes = vec![];
+    let mut seen = BTreeSet::new();
+    for existing in remote {
+        let recipient = match Recipient::of(existing) {
+            Some(r) => r,
+            None => continue,
+        };
+        // A recipient's managed jobs all go on its first notification, any others lose them
+        let first = seen.insert(recipient.clone());
+        let mut updated = existing.clone();
+        for event in EVENTS {
+            let ids = event.job_ids_mut(&mut updated);
+            ids.retain(|id| !desired.contains_key(id));
+            if first {
+                ids.extend(wanted(event, &recipient));
+            }
+            ids.sort();
+        }
+        if updated == *existing {
+            continue;
+        }
+        let empty =
+            EVENTS.iter().all(|e| e.job_ids(&updated).is_empty()) && updated.on_warning.is_empty();
+        changes.push(if empty {
+            NotificationChange::Delete(updated)
+        } else {
+            NotificationChange::Update(updated)
+        });
+    }
+
+    let missing: BTreeSet<_> = desired
+        .values()
+        .flatten()
+        .map(|(_, r)| r.clone())
+        .filter(|r| !seen.contains(r))
+        .collect();
+    for recipient in missing {
+        let mut created = Notification {
+            account_id,
+            state: 1,
+            ..Notification::default()
+        };
+        match &recipient {
+            Recipient::Email(email) => {
+                created.notification_type = Notification::EXTERNAL_EMAIL;
+                created.external_email = Some(email.to_string());
+            }
+            Recipient::Slack(id) => {
+                created.notification_type = Notification::SLACK;
+                created.slack_channel_id = Some(id.to_string());
+                created.slack_channel_name = slack_names.get(id).cloned();
+            }
+        }
+        for event in EVENTS {
+            *event.job_ids_mut(&mut created) = wanted(event, &recipient);
+        }
+        changes.push(NotificationChange::Create(created));
+    }
+    changes
+}
+
+pub fn apply(client: &dyn DbtCloudApi, changes: &[NotificationChange]) {
+    for change in changes {
+        match change {
+            NotificationChange::Create(n) => {
+                println!("creating notification: {}", Recipient::of(n).unwrap());
+                client
+                    .create_notification(n)
+                    .expect("failed to create notification");
+            }
+            NotificationChange::Update(n) => {
+                println!("updating notification: {}", n.id.unwrap());
+                client
+                    .update_notification(n)
+                    .expect("failed to update notification");
+            }
+            NotificationChange::Delete(n) => {
+                println!("deleting notification: {}", n.id.unwrap());
+                client
+                    .delete_notification(n)
+                    .expect("failed to delete notification");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{for_job, from_local, reconcile, Event, NotificationChange, Recipient};
+    use crate::{local::Notifications, remote::Notification};
+
+    #[test]
+    fn keeps_unmanaged_jobs() {
+        let local: Notifications = serde_yaml::from_str(
+            "
+on_failure:
+  emails: [oncall@example.com]
+  slack:
+    - C01
+    - id: C02
+      name: \"#data-alerts\"
+",
+        )
+        .unwrap();
+        let subscriptions = from_local(&local);
+        assert!(subscriptions.contains(&(Event::Failure, Recipient::Slack("C02".to_string()))));
+
+        // Set up by hand for job 7, which dbterra doesn't manage, and job 1, which it does
+        let existing = Notification {
+            id: Some(10),
+            account_id: 123,
+            notification_type: Notification::EXTERNAL_EMAIL,
+            external_email: Some("oncall@example.com".to_string()),
+            on_failure: vec![7],
+            on_success: vec![1],
+            ..Notification::default()
+        };
+        let mut desired = HashMap::new();
+        desired.insert(1, subscriptions.clone());
+        desired.insert(2, subscriptions);
+        let names = super::slack_names(&local);
+        let changes = reconcile(123, &[existing], &desired, &names);
+
+        assert_eq!(changes.len(), 3);
+        match &changes[0] {
+            NotificationChange::Update(n) => {
+                assert_eq!(n.on_failure, vec![1, 2, 7]);
+                assert!(n.on_success.is_empty());
+            }
+            other => panic!("expected an update, got {:?}", other),
+        }
+        match &changes[2] {
+            NotificationChange::Create(n) => {
+                assert_eq!(n.slack_channel_id.as_deref(), Some("C02"));
+                assert_eq!(n.slack_channel_name.as_deref(), Some("#data-alerts"));
+                assert_eq!(n.on_failure, vec![1, 2]);
+            }
+            other => panic!("expected a create, got {:?}", other),
+        }
+
+        let applied: Vec<_> = changes
+            .into_iter()
+            .map(|c| match c {
+                NotificationChange::Create(n) | NotificationChange::Update(n) => n,
+                NotificationChange::Delete(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(for_job(2, &applied), for_job(1, &applied));
+        assert!(reconcile(123, &applied, &desired, &names).is_empty());
+
+        // Managing job 1 with no notifications only drops it, job 7 keeps its email
+        desired.clear();
+        desired.insert(1, Default::default());
+        let changes = reconcile(123, &applied, &desired, &names);
+        assert!(changes
+            .iter()
+            .all(|c| matches!(c, NotificationChange::Update(_))));
+        assert!(matches!(&changes[0], NotificationChange::Update(n) if n.on_failure == vec![2, 7]));
+    }
+}
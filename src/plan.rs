@@ -5,72 +5,237 @@ use colored::Colorize;
 use crate::{
     config::Config,
     diff::Diff,
+    fields::FieldNames,
     local::Job as LocalJob,
-    local::Root,
-    remote::{DbtCloudClient, Job as RemoteJob},
+    local::{Environment, IgnoredField, OnRemove, Root},
+    lookup,
+    notifications::{self, Subscriptions},
+    remote::{DbtCloudApi, Job as RemoteJob},
+    resolve::Resolved,
+    schedule, versions,
 };
 
 pub struct Plan {
+    /// The key under `accounts:`, only shown when dbt_cloud.yml manages several accounts
+    account: Option<String>,
+    account_id: i64,
     projects: Vec<ProjectPlan>,
+    /// Subscriptions for every job with a `notifications` block, by project id and job name
+    notifications: Vec<(i64, String, Subscriptions)>,
+    slack_names: HashMap<String, String>,
+    warnings: Vec<String>,
+    /// Shown so it's clear which project or environment a name picked
+    resolved: Vec<Resolved>,
     // environments: Vec<EnvironmentPlan>, TODO: Implement for environments as well
 }
 
 impl Plan {
-    pub fn from(yaml: Root, client: &DbtCloudClient, config: &Config) -> Self {
+    pub fn from(yaml: Root, client: &dyn DbtCloudApi, config: &Config) -> Result<Self, String> {
+        let names = FieldNames::new(&yaml.environments);
+        let mut warnings = versions::check(&yaml, client)?;
+        let mut managed_notifications = vec![];
+        let mut slack_names = HashMap::new();
+        for project in yaml.projects.values() {
+            for (k, j) in &project.jobs {
+                if let Some(n) = j.notifications(project) {
+                    let name = RemoteJob::name_for(k, j);
+                    managed_notifications.push((project.id(), name, notifications::from_local(n)));
+                    slack_names.extend(notifications::slack_names(n));
+                }
+            }
+        }
+        // Only asked for when needed, the token might not be allowed to see them otherwise
+        let remote_notifications = if managed_notifications.is_empty() {
+            vec![]
+        } else {
+            client
+                .get_notifications()
+                .expect("failed to get remote notifications")
+                .data
+                .unwrap_or_default()
+        };
+
+        // Jobs can run after or defer to jobs in other projects, so look them all up front
+        let has_references = yaml.projects.values().any(|p| {
+            p.jobs
+                .values()
+                .any(|j| j.run_after.is_some() || j.defer_to_job.is_some())
+        });
+        let managed = lookup::managed_jobs(&yaml, config);
+        let all_remote_jobs = if has_references {
+            client
+                .get_jobs()
+                .expect("failed to get remote jobs")
+                .data
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        // `None` for jobs that are yet to be created
+        let remote_id = |target: &lookup::ManagedJob| {
+            all_remote_jobs
+                .iter()
+                .find(|r| r.project_id == target.job.project_id && r.name == target.job.name)
+                .and_then(|r| r.id)
+        };
+
         let changes: Vec<_> = yaml
             .projects
             .into_iter()
-            .map(|(k, project)| {
+            .map(|(k, project)| -> Result<ProjectPlan, String> {
                 // Fetch our remote jobs
                 let remote_jobs = client
-                    .get_jobs_for_project(project.id)
+                    .get_jobs_for_project(project.id())
                     .expect("failed to get remote jobs");
 
                 // Convert our local jobs to look like remote ones// Grab the local YAML jobs and the remote jobs for the project
-                let local_config = config.with_project_id(project.id);
-                let local_jobs: Vec<(String, LocalJob)> = project.jobs.into_iter().collect();
+                let local_config = config.with_project_id(project.id());
+                let mut ignored = HashMap::new();
+                let mut pending = HashMap::new();
+                let local_jobs: Vec<(String, LocalJob)> =
+                    project.jobs.clone().into_iter().collect();
                 let converted_local_jobs: Vec<_> = local_jobs
                     .into_iter()
-                    .map(|(k, j)| {
-                        RemoteJob::from_local_job(&k, j, &local_config, &yaml.environments)
+                    .map(|(key, j)| -> Result<RemoteJob, String> {
+                        check_references(&j, &yaml.environments)
+                            .map_err(|err| format!("{}.{}: {}", k, key, err))?;
+                        let run_after = j.run_after.clone();
+                        let defer_to_job = j.defer_to_job.clone();
+                        let enabled = j.enabled.unwrap_or(true);
+                        let mut fields = j.ignored_fields(&project);
+                        // Ignored triggers don't get to keep a disabled job running
+                        if !enabled {
+                            fields.retain(|f| {
+                                !matches!(f, IgnoredField::Ci | IgnoredField::Schedule)
+                            });
+                        }
+                        let mut job =
+                            RemoteJob::from_local_job(&key, j, &local_config, &yaml.environments);
+                        ignored.insert(job.name.to_string(), fields);
+                        let mut waiting = Pending::default();
+                        if let Some(run_after) = run_after {
+                            let target = lookup::find_run_after(&managed, &k, &run_after)
+                                .map_err(|err| format!("{}.{}: {}", k, key, err))?;
+                            let target_id = remote_id(target);
+                            // Still checked for disabled jobs so they can be turned back on later
+                            if enabled {
+                                if target_id.is_none() {
+                                    waiting.run_after = Some(target.id_key());
+                                }
+                                job.job_completion_trigger_condition =
+                                    Some(run_after.trigger(target.job.project_id, target_id));
+                            }
+                        }
+                        if let Some(reference) = defer_to_job {
+                            let target = lookup::find_defer_to_job(&managed, &k, &reference)
+                                .map_err(|err| format!("{}.{}: {}", k, key, err))?;
+                            let target_id = remote_id(target);
+                            if target_id.is_none() {
+                                waiting.defer_to_job = Some(target.id_key());
+                            }
+                            job.deferring_job_definition_id = target_id;
+                        }
+                        pending.insert(job.name.to_string(), waiting);
+                        Ok(job)
                     })
-                    .collect();
+                    .collect::<Result<_, _>>()?;
 
                 // Figure out which are updates, creates, and deletes
                 let job_types: HashMap<String, JobPlanType> = determine_job_plan_types_by_name(
                     converted_local_jobs,
                     remote_jobs.data.unwrap(),
+                    &ignored,
+                    project.on_remove.unwrap_or(OnRemove::Delete),
                 );
 
+                let notifications_diff = |plan_type: &JobPlanType| {
+                    let (name, job_id) = match plan_type {
+                        JobPlanType::Create(local) => (&local.name, None),
+                        JobPlanType::Update(_, remote) => (&remote.name, remote.id),
+                        JobPlanType::Deactivate(..) | JobPlanType::Delete(_) => return None,
+                    };
+                    let (_, _, wanted) = managed_notifications
+                        .iter()
+                        .find(|(p, n, _)| *p == project.id() && n == name)?;
+                    let current = job_id
+                        .map(|id| notifications::for_job(id, &remote_notifications))
+                        .unwrap_or_default();
+                    Some(Diff::from(
+                        notifications::to_value(&current),
+                        notifications::to_value(wanted),
+                    ))
+                };
+
                 // Create job plans
                 let job_diffs: Vec<_> = job_types
                     .into_values()
-                    .map(|plan_type| match &plan_type {
-                        JobPlanType::Update(remote, local) => {
-                            let diff = local.diff(remote);
-                            JobPlan { plan_type, diff }
-                        }
-                        JobPlanType::Create(local) => {
-                            let diff = RemoteJob::default().new_diff(local);
-                            JobPlan { plan_type, diff }
-                        }
-                        JobPlanType::Delete(remote) => {
-                            let diff = remote.diff(&RemoteJob::default());
-                            JobPlan { plan_type, diff }
+                    .map(|plan_type| {
+                        let diff = match &plan_type {
+                            JobPlanType::Update(remote, local) => {
+                                let paths: Vec<_> = ignored[&remote.name]
+                                    .iter()
+                                    .flat_map(|f| f.remote_paths())
+                                    .copied()
+                                    .collect();
+                                local.diff(remote).without(&paths).localize(&names)
+                            }
+                            JobPlanType::Create(local) => {
+                                RemoteJob::default().new_diff(local).localize(&names)
+                            }
+                            JobPlanType::Deactivate(deactivated, remote) => {
+                                remote.diff(deactivated).localize(&names)
+                            }
+                            JobPlanType::Delete(remote) => {
+                                remote.diff(&RemoteJob::default()).localize(&names)
+                            }
+                        };
+                        let notifications = notifications_diff(&plan_type);
+                        let pending = match &plan_type {
+                            JobPlanType::Deactivate(..) | JobPlanType::Delete(_) => None,
+                            _ => pending.remove(plan_type_name(&plan_type)),
+                        };
+                        JobPlan {
+                            plan_type,
+                            diff,
+                            notifications,
+                            pending: pending.unwrap_or_default(),
                         }
                     })
                     .collect();
 
+                let runs_per_day =
+                    runs_per_day_changes(&k, &job_diffs, &yaml.environments, &mut warnings);
+
                 // Add our job plans
-                ProjectPlan {
-                    project_id: project.id,
+                Ok(ProjectPlan {
+                    project_id: project.id(),
                     project_name: k,
+                    runs_per_day,
                     jobs: job_diffs,
-                }
+                })
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
+
+        let plan = Self {
+            account: None,
+            account_id: config.account_id,
+            projects: changes,
+            notifications: managed_notifications,
+            slack_names,
+            warnings,
+            resolved: vec![],
+        };
+        // Fail before anything is applied rather than halfway through
+        plan.apply_order()?;
+        Ok(plan)
+    }
 
-        Self { projects: changes }
+    pub fn for_account(self, account: Option<String>) -> Self {
+        Self { account, ..self }
+    }
+
+    pub fn with_resolved(self, resolved: Vec<Resolved>) -> Self {
+        Self { resolved, ..self }
     }
 
     pub fn has_changes(&self) -> bool {
@@ -78,15 +243,97 @@ impl Plan {
     }
 
     pub fn pretty_print(&self) {
+        if let Some(account) = &self.account {
+            println!(
+                "{}",
+                format!("account {} ({}):\n", account, self.account_id).bold()
+            );
+        }
+        for r in &self.resolved {
+            println!("{} {} \"{}\" has id {}", r.kind, r.key, r.name, r.id);
+        }
+        if !self.resolved.is_empty() {
+            println!();
+        }
+        for w in &self.warnings {
+            println!("{} {}", "warning:".yellow(), w);
+        }
+        if !self.warnings.is_empty() {
+            println!();
+        }
         for p in &self.projects {
             p.pretty_print();
         }
     }
 
-    pub fn apply(&self, client: &DbtCloudClient) {
-        for p in &self.projects {
-            p.apply(client);
+    pub fn apply(&self, client: &dyn DbtCloudApi) {
+        let mut created = HashMap::new();
+        for j in self.apply_order().expect("checked when planning") {
+            j.apply(client, &mut created);
         }
+        if self.has_notification_changes() {
+            self.apply_notifications(client);
+        }
+    }
+
+    /// Jobs that run after or defer to a job created in the same apply go after it, so they can
+    /// get its id
+    fn apply_order(&self) -> Result<Vec<&JobPlan>, String> {
+        let mut remaining: Vec<_> = self.projects.iter().flat_map(|p| &p.jobs).collect();
+        let mut order = vec![];
+        let mut done = HashSet::new();
+        while !remaining.is_empty() {
+            let (ready, blocked): (Vec<_>, Vec<_>) = remaining
+                .into_iter()
+                .partition(|j| j.pending.targets().all(|t| done.contains(t)));
+            if ready.is_empty() {
+                let names: Vec<_> = blocked.iter().map(|j| j.name()).collect();
+                return Err(format!(
+                    "run_after and defer_to_job go round in circles between {}",
+                    names.join(", ")
+                ));
+            }
+            for j in ready {
+                if let JobPlanType::Create(local) = &j.plan_type {
+                    done.insert((local.project_id, local.name.to_string()));
+                }
+                order.push(j);
+            }
+            remaining = blocked;
+        }
+        Ok(order)
+    }
+
+    fn has_notification_changes(&self) -> bool {
+        self.projects
+            .iter()
+            .flat_map(|p| &p.jobs)
+            .any(|j| j.notifications.as_ref().is_some_and(|n| n.has_changes()))
+    }
+
+    /// Runs after the jobs are applied so that new jobs have an id to be notified about
+    fn apply_notifications(&self, client: &dyn DbtCloudApi) {
+        let jobs = client.get_jobs().expect("failed to get remote jobs");
+        let desired = self
+            .notifications
+            .iter()
+            .filter_map(|(project_id, name, wanted)| {
+                let job = jobs
+                    .data
+                    .iter()
+                    .flatten()
+                    .find(|j| j.project_id == *project_id && j.name == *name)?;
+                Some((job.id?, wanted.clone()))
+            })
+            .collect();
+        let remote = client
+            .get_notifications()
+            .expect("failed to get remote notifications")
+            .data
+            .unwrap_or_default();
+        let changes =
+            notifications::reconcile(self.account_id, &remote, &desired, &self.slack_names);
+        notifications::apply(client, &changes);
     }
 }
 
@@ -94,6 +341,8 @@ pub struct ProjectPlan {
     project_id: i64,
     project_name: String,
     jobs: Vec<JobPlan>,
+    /// How many more or fewer scheduled runs a day each environment gets, by environment key
+    runs_per_day: Vec<(String, f64)>,
 }
 
 impl ProjectPlan {
@@ -107,17 +356,108 @@ impl ProjectPlan {
         for j in temp_jobs {
             j.pretty_print();
         }
+        for (environment, runs) in &self.runs_per_day {
+            let change = if *runs > 0.0 {
+                format!("+{} runs/day", schedule::format_runs(*runs)).green()
+            } else {
+                format!("{} runs/day", schedule::format_runs(*runs)).red()
+            };
+            println!("{} in {}", change, environment);
+        }
+    }
+}
+
+/// Only the environments whose scheduled runs change, sorted by key. Jobs with a cron that can't
+/// be read are left out with a warning rather than counted as never running.
+fn runs_per_day_changes(
+    project_key: &str,
+    jobs: &[JobPlan],
+    environments: &HashMap<String, Environment>,
+    warnings: &mut Vec<String>,
+) -> Vec<(String, f64)> {
+    let keys: HashMap<_, _> = environments
+        .iter()
+        .filter_map(|(k, e)| Some((e.id?, k.to_string())))
+        .collect();
+    let mut changes: HashMap<String, f64> = HashMap::new();
+    for j in jobs {
+        let (before, after) = match &j.plan_type {
+            JobPlanType::Create(local) => (None, Some(local)),
+            JobPlanType::Update(merged, remote) => (Some(remote), Some(merged)),
+            JobPlanType::Deactivate(deactivated, remote) => (Some(remote), Some(deactivated)),
+            JobPlanType::Delete(remote) => (Some(remote), None),
+        };
+        // Both sides or neither, half a job would make up a change
+        let counted: Result<Vec<_>, String> = before
+            .into_iter()
+            .map(|job| (job, -1.0))
+            .chain(after.into_iter().map(|job| (job, 1.0)))
+            .map(|(job, sign)| Ok((job, sign * schedule::runs_per_day(job)?)))
+            .collect();
+        let counted = match counted {
+            Ok(counted) => counted,
+            Err(err) => {
+                warnings.push(format!(
+                    "{}: runs/day leave out \"{}\", {}",
+                    project_key,
+                    plan_type_name(&j.plan_type),
+                    err
+                ));
+                continue;
+            }
+        };
+        for (job, runs) in counted {
+            let environment = keys
+                .get(&job.environment_id)
+                .cloned()
+                .unwrap_or_else(|| job.environment_id.to_string());
+            *changes.entry(environment).or_default() += runs;
+        }
+    }
+    let mut changes: Vec<_> = changes
+        .into_iter()
+        .filter(|(_, runs)| runs.abs() > 1e-9)
+        .collect();
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+/// Checks what can be checked without knowing about other jobs
+fn check_references(
+    job: &LocalJob,
+    environments: &HashMap<String, Environment>,
+) -> Result<(), String> {
+    if job.defer_to_job.is_some() && job.defer_to_job_id.is_some() {
+        return Err("set either defer_to_job or defer_to_job_id, not both".to_string());
     }
-    pub fn apply(&self, client: &DbtCloudClient) {
-        for j in &self.jobs {
-            j.apply(client);
+    if let Some(key) = &job.defer_to_environment {
+        if job.defer_to_env_id.is_some() {
+            return Err("set either defer_to_environment or defer_to_env_id, not both".to_string());
+        }
+        if !environments.contains_key(key) {
+            return Err(format!(
+                "defer_to_environment refers to `{}` but there's no such environment",
+                key
+            ));
         }
     }
+    Ok(())
+}
+
+fn plan_type_name(plan_type: &JobPlanType) -> &str {
+    match plan_type {
+        JobPlanType::Create(new) => &new.name,
+        JobPlanType::Update(_, remote) => &remote.name,
+        JobPlanType::Deactivate(_, remote) => &remote.name,
+        JobPlanType::Delete(remote) => &remote.name,
+    }
 }
 
 fn determine_job_plan_types_by_name(
     local_jobs: Vec<RemoteJob>,
     remote_jobs: Vec<RemoteJob>,
+    ignored: &HashMap<String, Vec<IgnoredField>>,
+    on_remove: OnRemove,
 ) -> HashMap<String, JobPlanType> {
     let local_keys: HashSet<String> = local_jobs.iter().map(|j| j.name.to_string()).collect();
     let remote_keys: HashSet<String> = remote_jobs.iter().map(|j| j.name.to_string()).collect();
@@ -141,37 +481,61 @@ fn determine_job_plan_types_by_name(
     for k in update_keys {
         let c = local_jobs_by_name.remove(k.as_str()).unwrap();
         let r = remote_jobs_by_name.remove(k.as_str()).unwrap();
-        matched.insert(k.to_string(), JobPlanType::Update(c.merge(&r), r));
+        let merged = c.merge(&r, &ignored[k.as_str()]);
+        matched.insert(k.to_string(), JobPlanType::Update(merged, r));
     }
     for k in delete_keys {
         let r = remote_jobs_by_name.remove(k.as_str()).unwrap();
-        matched.insert(k.to_string(), JobPlanType::Delete(r));
+        let plan_type = match on_remove {
+            OnRemove::Delete => JobPlanType::Delete(r),
+            OnRemove::Deactivate => JobPlanType::Deactivate(r.deactivated(), r),
+        };
+        matched.insert(k.to_string(), plan_type);
     }
     matched
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)] // plans are short-lived, boxing the jobs isn't worth it
 enum JobPlanType {
     Create(RemoteJob),
     Update(RemoteJob, RemoteJob),
+    /// Removed from dbt_cloud.yml in a project with `on_remove: deactivate`
+    Deactivate(RemoteJob, RemoteJob),
     Delete(RemoteJob),
 }
 
 struct JobPlan {
     plan_type: JobPlanType,
     diff: Diff,
+    /// `None` when the job's notifications aren't managed
+    notifications: Option<Diff>,
+    pending: Pending,
+}
+
+/// Jobs this one refers to that are yet to be created, by project id and name
+#[derive(Debug, Clone, Default)]
+struct Pending {
+    run_after: Option<(i64, String)>,
+    defer_to_job: Option<(i64, String)>,
+}
+
+impl Pending {
+    fn targets(&self) -> impl Iterator<Item = &(i64, String)> {
+        self.run_after.iter().chain(&self.defer_to_job)
+    }
 }
 
 impl JobPlan {
     pub fn has_changes(&self) -> bool {
-        self.diff.has_changes()
+        self.changes_job() || self.notifications.as_ref().is_some_and(|n| n.has_changes())
+    }
+    /// A job waiting on a new job's id always needs updating, even when nothing else changed
+    fn changes_job(&self) -> bool {
+        self.diff.has_changes() || self.pending.targets().next().is_some()
     }
     pub fn name(&self) -> &str {
-        match &self.plan_type {
-            JobPlanType::Create(new) => &new.name,
-            JobPlanType::Update(_, remote) => &remote.name,
-            JobPlanType::Delete(remote) => &remote.name,
-        }
+        plan_type_name(&self.plan_type)
     }
     pub fn pretty_print(&self) {
         if self.has_changes() {
@@ -179,6 +543,8 @@ impl JobPlan {
                 JobPlanType::Create(new) => {
                     println!("{}    \"{}\" (Computed)", "+".green(), new.name);
                     self.diff.pretty_print("      ");
+                    self.print_pending();
+                    self.print_notifications();
                 }
                 JobPlanType::Update(_, remote) => {
                     println!(
@@ -188,6 +554,17 @@ impl JobPlan {
                         remote.id.unwrap()
                     );
                     self.diff.pretty_print("      ");
+                    self.print_pending();
+                    self.print_notifications();
+                }
+                JobPlanType::Deactivate(_, remote) => {
+                    println!(
+                        "{}    \"{}\" ({}) (Deactivated)",
+                        "~".yellow(),
+                        remote.name,
+                        remote.id.unwrap()
+                    );
+                    self.diff.pretty_print("      ");
                 }
                 JobPlanType::Delete(remote) => {
                     println!(
@@ -201,18 +578,63 @@ impl JobPlan {
         }
     }
 
-    pub fn apply(&self, client: &DbtCloudClient) {
-        if !self.has_changes() {
+    fn print_pending(&self) {
+        if let Some((_, name)) = &self.pending.defer_to_job {
+            let value = format!("(known after apply, id of \"{}\")", name);
+            println!(
+                "      {} {} {}",
+                "+".green(),
+                "defer_to_job_id".green(),
+                value.green()
+            );
+        }
+    }
+
+    fn print_notifications(&self) {
+        if let Some(n) = &self.notifications {
+            n.pretty_print("      ");
+        }
+    }
+
+    /// Notifications are applied separately, see `Plan::apply_notifications`.
+    /// `created` holds the ids of jobs created so far by project id and name.
+    pub fn apply(&self, client: &dyn DbtCloudApi, created: &mut HashMap<(i64, String), i64>) {
+        if !self.changes_job() {
             return;
         }
+        let with_created_ids = |job: &RemoteJob| {
+            let mut job = job.clone();
+            if let (Some(target), Some(trigger)) = (
+                &self.pending.run_after,
+                &mut job.job_completion_trigger_condition,
+            ) {
+                trigger.condition.job_id = created.get(target).copied();
+            }
+            if let Some(target) = &self.pending.defer_to_job {
+                job.deferring_job_definition_id = created.get(target).copied();
+            }
+            job
+        };
         match &self.plan_type {
             JobPlanType::Create(local) => {
                 println!("creating job: {}", &local.name);
-                client.create_job(local).expect("failed to create job");
+                let job = with_created_ids(local);
+                let new = client.create_job(&job).expect("failed to create job");
+                if let Some(id) = new.data.and_then(|j| j.id) {
+                    created.insert((job.project_id, job.name), id);
+                }
             }
             JobPlanType::Update(local, _) => {
                 println!("updating job: {}", local.id.unwrap());
-                client.update_job(local).expect("failed to update job");
+                client
+                    .update_job(&with_created_ids(local))
+                    .expect("failed to update job");
+            }
+            JobPlanType::Deactivate(deactivated, _) => {
+                println!("deactivating job: {}", deactivated.id.unwrap());
+                client
+                    .update_job(deactivated)
+                    .expect("failed to deactivate job");
             }
             JobPlanType::Delete(remote) => {
                 println!("deleting job: {}", remote.id.unwrap());
@@ -221,3 +643,386 @@ impl JobPlan {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Plan;
+    use crate::{
+        config::Config,
+        local::{IgnoredField, OnRemove, Root},
+        remote::{DbtCloudApi, FakeDbtCloud},
+        testing::config,
+    };
+
+    fn root(threads: i64, with_seed: bool) -> Root {
+        let seed = if with_seed {
+            "
+      seed:
+        environment: bizops
+        target: production
+        steps:
+          - dbt seed"
+        } else {
+            ""
+        };
+        let yaml = format!(
+            "
+projects:
+  example:
+    id: 456
+    jobs:
+      full_run:
+        environment: bizops
+        target: production
+        threads: {}
+        steps:
+          - dbt run
+        schedule:
+          cron: \"0 9 * * *\"{}
+environments:
+  bizops:
+    id: 789
+",
+            threads, seed
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn apply_then_replan_has_no_changes() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+
+        let plan = Plan::from(root(8, true), &fake, &config).unwrap();
+        assert!(plan.has_changes());
+        plan.apply(&fake);
+        assert_eq!(fake.jobs().len(), 2);
+
+        let plan = Plan::from(root(8, true), &fake, &config).unwrap();
+        assert!(!plan.has_changes());
+    }
+
+    #[test]
+    fn update_and_delete() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        Plan::from(root(8, true), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+        let ids: Vec<_> = fake.jobs().iter().map(|j| j.id).collect();
+
+        let plan = Plan::from(root(16, false), &fake, &config).unwrap();
+        assert!(plan.has_changes());
+        plan.apply(&fake);
+
+        let jobs = fake.get_jobs_for_project(456).unwrap().data.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].name, "Full Run");
+        assert_eq!(jobs[0].settings.threads, 16);
+        assert!(ids.contains(&jobs[0].id), "updated job keeps its id");
+        assert!(!Plan::from(root(16, false), &fake, &config)
+            .unwrap()
+            .has_changes());
+    }
+
+    #[test]
+    fn runs_per_day_changes() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        let plan = Plan::from(root(8, false), &fake, &config).unwrap();
+        assert_eq!(
+            plan.projects[0].runs_per_day,
+            vec![("bizops".to_string(), 1.0)]
+        );
+        plan.apply(&fake);
+
+        let mut yaml = root(8, false);
+        let job = yaml
+            .projects
+            .get_mut("example")
+            .unwrap()
+            .jobs
+            .get_mut("full_run")
+            .unwrap();
+        job.schedule.as_mut().unwrap().cron = "*/15 * * * *".to_string();
+        let plan = Plan::from(yaml, &fake, &config).unwrap();
+        assert_eq!(
+            plan.projects[0].runs_per_day,
+            vec![("bizops".to_string(), 95.0)]
+        );
+        assert!(
+            Plan::from(root(8, false), &fake, &config).unwrap().projects[0]
+                .runs_per_day
+                .is_empty()
+        );
+
+        // Not counting a cron that can't be read would look like fewer runs
+        let mut yaml = root(8, false);
+        let job = yaml
+            .projects
+            .get_mut("example")
+            .unwrap()
+            .jobs
+            .get_mut("full_run")
+            .unwrap();
+        job.schedule.as_mut().unwrap().cron = "0 9 * * 1-8".to_string();
+        let plan = Plan::from(yaml, &fake, &config).unwrap();
+        assert!(plan.projects[0].runs_per_day.is_empty());
+        assert_eq!(
+            plan.warnings,
+            vec!["example: runs/day leave out \"Full Run\", `0 9 * * 1-8` has an invalid day of week field: `1-8` isn't within 0-7"]
+        );
+    }
+
+    #[test]
+    fn ignored_fields_keep_remote_values() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        Plan::from(root(8, false), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+
+        // Someone on call bumps threads and pauses the schedule in the UI
+        let mut tweaked = fake.get_jobs().unwrap().data.unwrap().remove(0);
+        tweaked.settings.threads = 32;
+        tweaked.triggers.schedule = false;
+        fake.update_job(&tweaked).unwrap();
+        assert!(Plan::from(root(8, false), &fake, &config)
+            .unwrap()
+            .has_changes());
+
+        let mut ignoring = root(8, false);
+        let project = ignoring.projects.get_mut("example").unwrap();
+        project.ignore_changes = Some(vec![IgnoredField::Threads]);
+        let job = project.jobs.get_mut("full_run").unwrap();
+        job.ignore_changes = Some(vec![IgnoredField::Schedule]);
+        let plan = Plan::from(ignoring.clone(), &fake, &config).unwrap();
+        assert!(!plan.has_changes());
+
+        // Other changes still go through, without touching the ignored fields
+        let job = ignoring
+            .projects
+            .get_mut("example")
+            .unwrap()
+            .jobs
+            .get_mut("full_run")
+            .unwrap();
+        job.generate_docs = Some(true);
+        Plan::from(ignoring, &fake, &config).unwrap().apply(&fake);
+        let applied = fake.get_jobs().unwrap().data.unwrap().remove(0);
+        assert!(applied.generate_docs);
+        assert_eq!(applied.settings.threads, 32);
+        assert!(!applied.triggers.schedule);
+    }
+
+    #[test]
+    fn disabled_and_removed_jobs_are_kept() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        Plan::from(root(8, true), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+        let full_run = |fake: &FakeDbtCloud| {
+            fake.jobs()
+                .into_iter()
+                .find(|j| j.name == "Full Run")
+                .unwrap()
+        };
+
+        // Paused, even with the schedule ignored
+        let mut yaml = root(8, true);
+        let job = yaml
+            .projects
+            .get_mut("example")
+            .unwrap()
+            .jobs
+            .get_mut("full_run")
+            .unwrap();
+        job.enabled = Some(false);
+        job.ignore_changes = Some(vec![IgnoredField::Schedule]);
+        Plan::from(yaml.clone(), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+        assert!(!full_run(&fake).triggers.schedule);
+        assert!(!Plan::from(yaml, &fake, &config).unwrap().has_changes());
+
+        // Removed, but only deactivated
+        let mut yaml = root(8, false);
+        yaml.projects.get_mut("example").unwrap().on_remove = Some(OnRemove::Deactivate);
+        Plan::from(root(8, true), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+        let seed_id = fake.jobs().iter().find(|j| j.name == "Seed").unwrap().id;
+        let mut seed = fake.get_jobs().unwrap().data.unwrap();
+        seed.retain(|j| j.id == seed_id);
+        seed[0].triggers.github_webhook = true;
+        fake.update_job(&seed[0]).unwrap();
+        let plan = Plan::from(yaml.clone(), &fake, &config).unwrap();
+        assert!(plan.has_changes());
+        plan.apply(&fake);
+        assert_eq!(fake.jobs().len(), 2);
+        assert!(!Plan::from(yaml, &fake, &config).unwrap().has_changes());
+
+        // Adding it back turns it back on, under the same id
+        Plan::from(root(8, true), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+        assert!(full_run(&fake).triggers.schedule);
+        let seed = fake.jobs().into_iter().find(|j| j.name == "Seed").unwrap();
+        assert_eq!(seed.id, seed_id);
+        assert!(!seed.triggers.github_webhook);
+    }
+
+    #[test]
+    fn accounts_are_planned_separately() {
+        let us = config();
+        let eu = Config {
+            account_id: 321,
+            ..config()
+        };
+        let fake = FakeDbtCloud::new(us.account_id);
+        let eu_fake = fake.for_account(eu.account_id);
+
+        Plan::from(root(8, true), &fake, &us).unwrap().apply(&fake);
+        let plan = Plan::from(root(8, false), &eu_fake, &eu)
+            .unwrap()
+            .for_account(Some("eu".to_string()));
+        assert!(plan.has_changes(), "jobs in another account don't count");
+        plan.apply(&eu_fake);
+
+        assert_eq!(fake.jobs().len(), 3);
+        assert_eq!(eu_fake.get_jobs().unwrap().data.unwrap().len(), 1);
+        assert!(!Plan::from(root(8, true), &fake, &us).unwrap().has_changes());
+        assert!(!Plan::from(root(8, false), &eu_fake, &eu)
+            .unwrap()
+            .has_changes());
+    }
+
+    #[test]
+    fn notifications_follow_jobs() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        let mut yaml = root(8, true);
+        let project = yaml.projects.get_mut("example").unwrap();
+        project.notifications = Some(
+            serde_yaml::from_str("on_failure: {emails: [oncall@example.com], slack: [C01]}")
+                .unwrap(),
+        );
+        project.jobs.get_mut("seed").unwrap().notifications = Some(Default::default());
+
+        let plan = Plan::from(yaml.clone(), &fake, &config).unwrap();
+        plan.apply(&fake);
+        let full_run = fake
+            .jobs()
+            .into_iter()
+            .find(|j| j.name == "Full Run")
+            .unwrap();
+        let notifications = fake.notifications();
+        assert_eq!(notifications.len(), 2);
+        assert!(notifications
+            .iter()
+            .all(|n| n.on_failure == vec![full_run.id.unwrap()]));
+        assert!(!Plan::from(yaml.clone(), &fake, &config)
+            .unwrap()
+            .has_changes());
+
+        // Dropping a recipient is a change to the job's plan
+        let project = yaml.projects.get_mut("example").unwrap();
+        project
+            .notifications
+            .as_mut()
+            .unwrap()
+            .on_failure
+            .slack
+            .clear();
+        let plan = Plan::from(yaml.clone(), &fake, &config).unwrap();
+        assert!(plan.has_changes());
+        plan.apply(&fake);
+        assert_eq!(fake.notifications().len(), 1);
+        assert!(!Plan::from(yaml, &fake, &config).unwrap().has_changes());
+    }
+
+    #[test]
+    fn run_after_jobs_created_together() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        let mut yaml = root(8, true);
+        let jobs = &mut yaml.projects.get_mut("example").unwrap().jobs;
+        jobs.get_mut("full_run").unwrap().run_after =
+            Some(serde_yaml::from_str("{job: seed, statuses: [success, error]}").unwrap());
+
+        let plan = Plan::from(yaml.clone(), &fake, &config).unwrap();
+        assert_eq!(plan.apply_order().unwrap()[1].name(), "Full Run");
+        plan.apply(&fake);
+        let jobs = fake.jobs();
+        let seed = jobs.iter().find(|j| j.name == "Seed").unwrap();
+        let full_run = jobs.iter().find(|j| j.name == "Full Run").unwrap();
+        let condition = &full_run
+            .job_completion_trigger_condition
+            .as_ref()
+            .unwrap()
+            .condition;
+        assert_eq!(condition.job_id, seed.id);
+        assert_eq!(condition.project_id, 456);
+        assert_eq!(condition.statuses, vec![10, 20]);
+        assert!(!Plan::from(yaml.clone(), &fake, &config)
+            .unwrap()
+            .has_changes());
+
+        let jobs = &mut yaml.projects.get_mut("example").unwrap().jobs;
+        jobs.get_mut("seed").unwrap().run_after =
+            Some(serde_yaml::from_str("{job: Full Run}").unwrap());
+        let fresh = FakeDbtCloud::new(config.account_id);
+        let circular = Plan::from(yaml, &fresh, &config).err().unwrap();
+        assert!(circular.starts_with("run_after and defer_to_job go round in circles"));
+    }
+
+    #[test]
+    fn defer_to_jobs_by_key() {
+        let config = config();
+        let fake = FakeDbtCloud::new(config.account_id);
+        let mut yaml = root(8, true);
+        let jobs = &mut yaml.projects.get_mut("example").unwrap().jobs;
+        let full_run = jobs.get_mut("full_run").unwrap();
+        full_run.defer_to_job = Some("example.seed".to_string());
+        full_run.defer_to_environment = Some("bizops".to_string());
+
+        let plan = Plan::from(yaml.clone(), &fake, &config).unwrap();
+        assert_eq!(plan.apply_order().unwrap()[1].name(), "Full Run");
+        plan.apply(&fake);
+        let jobs = fake.jobs();
+        let seed = jobs.iter().find(|j| j.name == "Seed").unwrap();
+        let full_run = jobs.iter().find(|j| j.name == "Full Run").unwrap();
+        assert_eq!(full_run.deferring_job_definition_id, seed.id);
+        assert_eq!(full_run.deferring_environment_id, Some(789));
+        assert!(!Plan::from(yaml.clone(), &fake, &config)
+            .unwrap()
+            .has_changes());
+
+        // Recreating the job it defers to moves the deferral along with it
+        let seed = seed.clone();
+        fake.delete_job(&seed).unwrap();
+        Plan::from(yaml.clone(), &fake, &config)
+            .unwrap()
+            .apply(&fake);
+        let jobs = fake.jobs();
+        let new_seed = jobs.iter().find(|j| j.name == "Seed").unwrap();
+        let full_run = jobs.iter().find(|j| j.name == "Full Run").unwrap();
+        assert_ne!(new_seed.id, seed.id);
+        assert_eq!(full_run.deferring_job_definition_id, new_seed.id);
+
+        let full_run = yaml
+            .projects
+            .get_mut("example")
+            .unwrap()
+            .jobs
+            .get_mut("full_run")
+            .unwrap();
+        full_run.defer_to_job = Some("nightly".to_string());
+        let dangling = Plan::from(yaml.clone(), &fake, &config).err().unwrap();
+        assert_eq!(
+            dangling,
+            "example.full_run: defer_to_job refers to `nightly` but there's no such job in project `example`"
+        );
+    }
+}
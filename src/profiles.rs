@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+const PROFILES_FILE_ENV: &str = "DBTERRA_PROFILES_FILE";
+/// The profile to use when neither `--profile` nor the account under `accounts:` picks one
+pub const PROFILE_ENV: &str = "DBTERRA_PROFILE";
+
+/// Named accounts and credentials, read from `~/.config/dbterra/profiles.yml`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub base_url: Option<String>,
+    pub account_id: Option<i64>,
+    pub token: Option<Token>,
+}
+
+/// Either the token itself or where to get it from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Token {
+    Literal(String),
+    Source(TokenSource),
+}
+
+/// Exactly one of these should be set
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenSource {
+    /// The token itself
+    pub value: Option<String>,
+    /// Name of an environment variable holding the token
+    pub env: Option<String>,
+    /// Path of a file holding the token
+    pub file: Option<PathBuf>,
+    /// Shell command printing the token, e.g. a secrets manager CLI
+    pub command: Option<String>,
+}
+
+impl Profiles {
+    /// `DBTERRA_PROFILES_FILE`, or `profiles.yml` in the `dbterra` config directory
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(path) = env::var(PROFILES_FILE_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = env::var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| env::var("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .ok()?;
+        Some(config_dir.join("dbterra").join("profiles.yml"))
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let path = Profiles::path().ok_or("couldn't find a config directory for profiles.yml")?;
+        let f = std::fs::File::open(&path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        serde_yaml::from_reader(f)
+            .map_err(|err| format!("failed to parse {}: {}", path.display(), err))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Profile, String> {
+        self.profiles.get(name).ok_or_else(|| {
+            let mut names: Vec<_> = self.profiles.keys().map(|k| k.as_str()).collect();
+            names.sort();
+            format!(
+                "no profile named `{}`, available profiles: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+impl Token {
+    pub fn resolve(&self) -> Result<String, String> {
+        let source = match self {
+            Token::Literal(token) => return Ok(token.to_string()),
+            Token::Source(source) => source,
+        };
+        let token = match source {
+            TokenSource {
+                value: Some(value),
+                env: None,
+                file: None,
+                command: None,
+            } => value.to_string(),
+            TokenSource {
+                value: None,
+                env: Some(name),
+                file: None,
+                command: None,
+            } => env::var(name).map_err(|_| format!("{} must be set", name))?,
+            TokenSource {
+                value: None,
+                env: None,
+                file: Some(path),
+                command: None,
+            } => std::fs::read_to_string(path)
+                .map_err(|err| format!("failed to read token from {}: {}", path.display(), err))?,
+            TokenSource {
+                value: None,
+                env: None,
+                file: None,
+                command: Some(command),
+            } => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .map_err(|err| format!("failed to run `{}`: {}", command, err))?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "`{}` failed: {}",
+                        command,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                String::from_utf8_lossy(&output.stdout).to_string()
+            }
+            _ => {
+                return Err(
+                    "a token needs exactly one of `value`, `env`, `file` or `command`".to_string(),
+                )
+            }
+        };
+        Ok(token.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+
+    use super::{Profiles, Token, TokenSource};
+
+    #[test]
+    fn parses_profiles() {
+        let profiles: Profiles = serde_yaml::from_str(
+            "
+profiles:
+  us:
+    base_url: https://xyz.us1.dbt.com
+    account_id: 123
+    token: abc
+  eu:
+    account_id: 456
+    token:
+      command: echo def
+",
+        )
+        .unwrap();
+        let us = profiles.get("us").unwrap();
+        assert_eq!(us.account_id, Some(123));
+        assert_eq!(us.token.as_ref().unwrap().resolve().unwrap(), "abc");
+        let eu = profiles.get("eu").unwrap();
+        assert_eq!(eu.base_url, None);
+        assert_eq!(eu.token.as_ref().unwrap().resolve().unwrap(), "def");
+        assert_eq!(
+            profiles.get("apac").unwrap_err(),
+            "no profile named `apac`, available profiles: eu, us"
+        );
+    }
+
+    #[test]
+    fn token_sources() {
+        let path = env::var("PATH").unwrap();
+        let from_env = Token::Source(TokenSource {
+            env: Some("PATH".to_string()),
+            ..TokenSource::default()
+        });
+        assert_eq!(from_env.resolve().unwrap(), path.trim());
+        let unset = Token::Source(TokenSource {
+            env: Some("DBTERRA_TEST_UNSET_TOKEN".to_string()),
+            ..TokenSource::default()
+        });
+        assert_eq!(
+            unset.resolve().unwrap_err(),
+            "DBTERRA_TEST_UNSET_TOKEN must be set"
+        );
+
+        let from_file = Token::Source(TokenSource {
+            file: Some(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml")),
+            ..TokenSource::default()
+        });
+        assert_eq!(
+            from_file.resolve().unwrap(),
+            include_str!("../Cargo.toml").trim()
+        );
+
+        let failing = Token::Source(TokenSource {
+            command: Some("exit 1".to_string()),
+            ..TokenSource::default()
+        });
+        assert!(failing.resolve().is_err());
+
+        let ambiguous = Token::Source(TokenSource {
+            value: Some("abc".to_string()),
+            env: Some("PATH".to_string()),
+            ..TokenSource::default()
+        });
+        assert!(ambiguous.resolve().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::error::Error;
+
+use crate::remote::types::{
+    Account, DbtVersion, Environment, Job, Notification, Project, Response, Run, TriggerRun,
+};
+
+/// Everything dbterra needs from a dbt Cloud account, implemented by the HTTP client and by fakes
+pub trait DbtCloudApi {
+    fn get_jobs(&self) -> Result<Response<Vec<Job>>, Box<dyn Error>>;
+
+    fn create_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>>;
+
+    fn update_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>>;
+
+    fn delete_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>>;
+
+    fn trigger_run(
+        &self,
+        job_id: i64,
+        trigger: &TriggerRun,
+    ) -> Result<Response<Run>, Box<dyn Error>>;
+
+    /// The most recent runs of a job, newest first, optionally only those with the given status
+    fn get_runs(
+        &self,
+        job_id: i64,
+        status: Option<i64>,
+        limit: usize,
+    ) -> Result<Response<Vec<Run>>, Box<dyn Error>>;
+
+    /// The raw contents of an artifact like `manifest.json` produced by a run
+    fn get_run_artifact(&self, run_id: i64, path: &str) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Includes the run's steps
+    fn get_run(&self, run_id: i64) -> Result<Response<Run>, Box<dyn Error>>;
+
+    fn get_notifications(&self) -> Result<Response<Vec<Notification>>, Box<dyn Error>>;
+
+    fn create_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>>;
+
+    fn update_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>>;
+
+    fn delete_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>>;
+
+    /// The dbt versions the account can use, release tracks like `latest` included
+    fn get_dbt_versions(&self) -> Result<Response<Vec<DbtVersion>>, Box<dyn Error>>;
+
+    fn get_environment(
+        &self,
+        project_id: i64,
+        environment_id: i64,
+    ) -> Result<Response<Environment>, Box<dyn Error>>;
+
+    fn get_projects(&self) -> Result<Response<Vec<Project>>, Box<dyn Error>>;
+
+    /// Environments of every project in the account
+    fn get_environments(&self) -> Result<Response<Vec<Environment>>, Box<dyn Error>>;
+
+    fn get_account(&self) -> Result<Response<Account>, Box<dyn Error>>;
+
+    fn get_jobs_for_project(&self, project_id: i64) -> Result<Response<Vec<Job>>, Box<dyn Error>> {
+        let dbt_response = self.get_jobs()?;
+        let filtered_response = Response {
+            status: dbt_response.status,
+            data: Some(
+                dbt_response
+                    .data
+                    .expect("error gettings jobs, check `status`")
+                    .into_iter()
+                    .filter(|j| j.project_id == project_id)
+                    .collect(),
+            ),
+            extra: None,
+        };
+        Ok(filtered_response)
+    }
+}
@@ -1,16 +1,24 @@
 // API Docs
 // https://docs.getdbt.com/dbt-cloud/api-v2
 
-use std::error::Error;
 use colored::Colorize;
+use std::error::Error;
+use std::thread::sleep;
 
 use reqwest::blocking::{Client, RequestBuilder};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, USER_AGENT};
-use reqwest::Method;
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::config::Config;
-use crate::remote::types::{Job, Response};
+use crate::remote::api::DbtCloudApi;
+use crate::remote::types::{
+    Account, DbtVersion, Environment, Job, Notification, Project, Response, Run, Status, TriggerRun,
+};
+
+/// The largest page dbt Cloud hands out for list endpoints
+const PAGE_SIZE: usize = 100;
 
 pub struct DbtCloudClient<'a> {
     pub client: Client,
@@ -46,82 +54,375 @@ impl<'a> DbtCloudClient<'a> {
         builder
     }
 
-    pub fn get_jobs(&self) -> Result<Response<Vec<Job>>, Box<dyn Error>> {
+    /// Sends the request, retrying transient failures with backoff. Only use for idempotent calls.
+    fn send<T>(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<T>,
+    ) -> reqwest::Result<reqwest::blocking::Response>
+    where
+        T: Serialize,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = self.request(method.clone(), url, body.as_ref()).send();
+            match self.config.retry.should_retry(attempt, &result) {
+                Some(delay) => {
+                    log_retry(url, attempt, &result);
+                    sleep(delay);
+                    attempt += 1;
+                }
+                None => return result,
+            }
+        }
+    }
+
+    /// Retried like `send`, but a 404 after a failed attempt means that attempt deleted it and
+    /// only its response got lost, so it counts as deleted
+    fn delete<T>(&self, url: &str) -> Result<Response<T>, Box<dyn Error>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = self.request::<()>(Method::DELETE, url, None).send();
+            if attempt > 0 && matches!(&result, Ok(r) if r.status() == StatusCode::NOT_FOUND) {
+                return Ok(Response {
+                    data: None,
+                    status: Status {
+                        code: 200,
+                        is_success: true,
+                        ..Status::default()
+                    },
+                    extra: None,
+                });
+            }
+            match self.config.retry.should_retry(attempt, &result) {
+                Some(delay) => {
+                    log_retry(url, attempt, &result);
+                    sleep(delay);
+                    attempt += 1;
+                }
+                None => return Ok(log_when_error(result?)?.json()?),
+            }
+        }
+    }
+
+    /// Follows a list endpoint's pages until it has everything dbt Cloud says there is
+    fn get_all<T>(&self, path: &str) -> Result<Response<Vec<T>>, Box<dyn Error>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut items = vec![];
+        loop {
+            let url = format!(
+                "{}/api/v2/accounts/{}/{}/?offset={}&limit={}",
+                self.config.base_url,
+                self.config.account_id,
+                path,
+                items.len(),
+                PAGE_SIZE,
+            );
+
+            let response = self.send::<()>(Method::GET, &url, None)?;
+            let dbt_response: Response<Vec<T>> = log_when_error(response)?.json()?;
+            let page = dbt_response.data.unwrap_or_default();
+            let page_size = page.len();
+            items.extend(page);
+
+            let total_count = dbt_response
+                .extra
+                .and_then(|e| e.pagination)
+                .map(|p| p.total_count);
+            match total_count {
+                Some(total_count) if page_size > 0 && items.len() < total_count => {}
+                _ => {
+                    return Ok(Response {
+                        data: Some(items),
+                        status: dbt_response.status,
+                        extra: None,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Looks for a job with the same name in the same project, used to make retried creates safe
+    fn find_job_by_name(&self, job: &Job) -> Result<Option<Job>, Box<dyn Error>> {
+        let existing = self
+            .get_jobs_for_project(job.project_id)?
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .find(|j| j.name == job.name);
+        Ok(existing)
+    }
+}
+
+impl<'a> DbtCloudApi for DbtCloudClient<'a> {
+    fn get_jobs(&self) -> Result<Response<Vec<Job>>, Box<dyn Error>> {
+        self.get_all("jobs")
+    }
+
+    fn create_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
         let url = format!(
             "{}/api/v2/accounts/{}/jobs/",
+            self.config.base_url, self.config.account_id,
+        );
+        // Creating isn't idempotent, so before every retry make sure the job didn't get created anyway
+        let mut attempt = 0;
+        loop {
+            let result = self.request(Method::POST, &url, Some(job)).send();
+            match self.config.retry.should_retry(attempt, &result) {
+                Some(delay) => {
+                    log_retry(&url, attempt, &result);
+                    sleep(delay);
+                    attempt += 1;
+                    if let Some(existing) = self.find_job_by_name(job)? {
+                        return Ok(Response {
+                            data: Some(existing),
+                            status: Status {
+                                code: 200,
+                                is_success: true,
+                                ..Status::default()
+                            },
+                            extra: None,
+                        });
+                    }
+                }
+                None => {
+                    let dbt_response = log_when_error(result?)?.json()?;
+                    return Ok(dbt_response);
+                }
+            }
+        }
+    }
+
+    fn update_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/jobs/{}/",
             self.config.base_url,
             self.config.account_id,
+            job.id.expect("id is required to update a job"),
         );
-
-        let response = self.request::<Job>(Method::GET, &url, None).send()?;
+        let response = self.send(Method::POST, &url, Some(job))?;
         let dbt_response = log_when_error(response)?.json()?;
         Ok(dbt_response)
     }
 
-    pub fn get_jobs_for_project(
-        &self,
-        project_id: i64,
-    ) -> Result<Response<Vec<Job>>, Box<dyn Error>> {
-        let dbt_response = self.get_jobs()?;
-        let filtered_response = Response {
-            status: dbt_response.status,
-            data: Some(
-                dbt_response
-                    .data
-                    .expect("error gettings jobs, check `status`")
-                    .into_iter()
-                    .filter(|j| j.project_id == project_id)
-                    .collect(),
-            ),
-        };
-        Ok(filtered_response)
-    }
-
-    pub fn create_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+    fn delete_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
         let url = format!(
-            "{}/api/v2/accounts/{}/jobs/",
+            "{}/api/v2/accounts/{}/jobs/{}/",
             self.config.base_url,
             self.config.account_id,
+            job.id.expect("id is required to update a job"),
+        );
+        self.delete(&url)
+    }
+
+    fn trigger_run(
+        &self,
+        job_id: i64,
+        trigger: &TriggerRun,
+    ) -> Result<Response<Run>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/jobs/{}/run/",
+            self.config.base_url, self.config.account_id, job_id,
+        );
+        // Never retried, a duplicate run is worse than a failed command
+        let response = self.request(Method::POST, &url, Some(trigger)).send()?;
+        let dbt_response = log_when_error(response)?.json()?;
+        Ok(dbt_response)
+    }
+
+    fn get_runs(
+        &self,
+        job_id: i64,
+        status: Option<i64>,
+        limit: usize,
+    ) -> Result<Response<Vec<Run>>, Box<dyn Error>> {
+        let mut runs = vec![];
+        loop {
+            let page_limit = (limit - runs.len()).min(PAGE_SIZE);
+            let mut url = format!(
+                "{}/api/v2/accounts/{}/runs/?job_definition_id={}&order_by=-id&offset={}&limit={}",
+                self.config.base_url,
+                self.config.account_id,
+                job_id,
+                runs.len(),
+                page_limit,
+            );
+            if let Some(status) = status {
+                url.push_str(&format!("&status={}", status));
+            }
+            let response = self.send::<Run>(Method::GET, &url, None)?;
+            let dbt_response: Response<Vec<Run>> = log_when_error(response)?.json()?;
+            let page = dbt_response.data.unwrap_or_default();
+            let page_size = page.len();
+            runs.extend(page);
+            // A short page is the last one
+            if page_size < page_limit || runs.len() >= limit {
+                return Ok(Response {
+                    data: Some(runs),
+                    status: dbt_response.status,
+                    extra: None,
+                });
+            }
+        }
+    }
+
+    fn get_run_artifact(&self, run_id: i64, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/runs/{}/artifacts/{}",
+            self.config.base_url, self.config.account_id, run_id, path,
+        );
+        let response = self.send::<Run>(Method::GET, &url, None)?;
+        // Not every run produces every artifact, e.g. no catalog.json without docs
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("run {} has no {}", run_id, path).into());
+        }
+        let bytes = log_when_error(response)?.bytes()?;
+        Ok(bytes.to_vec())
+    }
+
+    fn get_run(&self, run_id: i64) -> Result<Response<Run>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/runs/{}/?include_related=%5B%22run_steps%22%5D",
+            self.config.base_url, self.config.account_id, run_id,
         );
-        let response = self.request(Method::POST, &url, Some(job)).send()?;
+        let response = self.send::<Run>(Method::GET, &url, None)?;
         let dbt_response = log_when_error(response)?.json()?;
         Ok(dbt_response)
     }
 
-    pub fn update_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+    fn get_notifications(&self) -> Result<Response<Vec<Notification>>, Box<dyn Error>> {
+        self.get_all("notifications")
+    }
+
+    fn create_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>> {
         let url = format!(
-            "{}/api/v2/accounts/{}/jobs/{}/",
+            "{}/api/v2/accounts/{}/notifications/",
+            self.config.base_url, self.config.account_id,
+        );
+        // Not retried, a duplicate notification means every email is sent twice
+        let response = self
+            .request(Method::POST, &url, Some(notification))
+            .send()?;
+        let dbt_response = log_when_error(response)?.json()?;
+        Ok(dbt_response)
+    }
+
+    fn update_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/notifications/{}/",
             self.config.base_url,
             self.config.account_id,
-            job.id.expect("id is required to update a job"),
+            notification
+                .id
+                .expect("id is required to update a notification"),
         );
-        let response = self.request(Method::POST, &url, Some(job)).send()?;
+        let response = self.send(Method::POST, &url, Some(notification))?;
         let dbt_response = log_when_error(response)?.json()?;
         Ok(dbt_response)
     }
 
-    pub fn delete_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+    fn delete_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>> {
         let url = format!(
-            "{}/api/v2/accounts/{}/jobs/{}/",
+            "{}/api/v2/accounts/{}/notifications/{}/",
             self.config.base_url,
             self.config.account_id,
-            job.id.expect("id is required to update a job"),
+            notification
+                .id
+                .expect("id is required to delete a notification"),
+        );
+        self.delete(&url)
+    }
+
+    fn get_dbt_versions(&self) -> Result<Response<Vec<DbtVersion>>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/versions/",
+            self.config.base_url, self.config.account_id,
+        );
+        let response = self.send::<DbtVersion>(Method::GET, &url, None)?;
+        let dbt_response = log_when_error(response)?.json()?;
+        Ok(dbt_response)
+    }
+
+    fn get_environment(
+        &self,
+        project_id: i64,
+        environment_id: i64,
+    ) -> Result<Response<Environment>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v3/accounts/{}/projects/{}/environments/{}/",
+            self.config.base_url, self.config.account_id, project_id, environment_id,
         );
-        let response = self.request::<Job>(Method::DELETE, &url, None).send()?;
+        let response = self.send::<Environment>(Method::GET, &url, None)?;
+        let dbt_response = log_when_error(response)?.json()?;
+        Ok(dbt_response)
+    }
+
+    fn get_projects(&self) -> Result<Response<Vec<Project>>, Box<dyn Error>> {
+        self.get_all("projects")
+    }
+
+    fn get_environments(&self) -> Result<Response<Vec<Environment>>, Box<dyn Error>> {
+        self.get_all("environments")
+    }
+
+    fn get_account(&self) -> Result<Response<Account>, Box<dyn Error>> {
+        let url = format!(
+            "{}/api/v2/accounts/{}/",
+            self.config.base_url, self.config.account_id,
+        );
+        let response = self.send::<()>(Method::GET, &url, None)?;
         let dbt_response = log_when_error(response)?.json()?;
         Ok(dbt_response)
     }
 }
 
-fn log_when_error(response: reqwest::blocking::Response) -> reqwest::Result<reqwest::blocking::Response> {
+fn log_when_error(
+    response: reqwest::blocking::Response,
+) -> reqwest::Result<reqwest::blocking::Response> {
     match response.error_for_status() {
         Ok(res) => Ok(res),
         Err(err) => {
             if err.url().is_some() && err.status().is_some() {
-                let error = format!("request to {} failed with http status code of {}", err.url().unwrap().path(), err.status().unwrap());
+                let error = format!(
+                    "request to {} failed with http status code of {}",
+                    err.url().unwrap().path(),
+                    err.status().unwrap()
+                );
                 println!("{}", error.red());
             }
             Err(err)
         }
     }
 }
+
+fn log_retry(url: &str, attempt: u32, result: &reqwest::Result<reqwest::blocking::Response>) {
+    let reason = match result {
+        Ok(res) => format!("http status code of {}", res.status()),
+        Err(err) => err.to_string(),
+    };
+    let path = reqwest::Url::parse(url)
+        .map(|u| u.path().to_string())
+        .unwrap_or_else(|_| url.to_string());
+    let message = format!(
+        "request to {} failed with {}, retrying (attempt {})",
+        path,
+        reason,
+        attempt + 1
+    );
+    println!("{}", message.yellow());
+}
@@ -0,0 +1,412 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::remote::api::DbtCloudApi;
+use crate::remote::types::{
+    Account, DbtVersion, Environment, Job, Notification, Project, Response, Run, RunStep,
+    RunTrigger, Status, TriggerRun,
+};
+
+/// What a fake account offers unless its snapshot says otherwise
+const DBT_VERSIONS: [&str; 5] = [
+    "1.6.0-latest",
+    "1.7.0-latest",
+    "1.8.0-latest",
+    "latest",
+    "versionless",
+];
+
+/// Run slots of accounts the snapshot doesn't list
+const RUN_SLOTS: i64 = 5;
+
+/// What a fake account looks like on disk, see `--backend fake:<snapshot.json>`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+    #[serde(default)]
+    pub runs: Vec<Run>,
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    #[serde(default)]
+    pub notifications: Vec<Notification>,
+    /// Environments not listed here exist too, on the environment's default version
+    #[serde(default)]
+    pub environments: Vec<Environment>,
+    #[serde(default)]
+    pub dbt_versions: Vec<DbtVersion>,
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub run_id: i64,
+    pub path: String,
+    pub contents: String,
+}
+
+/// An in-memory dbt Cloud account. When backed by a snapshot file every change is written back,
+/// so a `plan` after an `apply` sees what was applied. Accounts made with `for_account` share
+/// the same snapshot.
+pub struct FakeDbtCloud {
+    account_id: i64,
+    state: Arc<Mutex<Snapshot>>,
+    path: Option<PathBuf>,
+}
+
+impl FakeDbtCloud {
+    pub fn new(account_id: i64) -> Self {
+        Self {
+            account_id,
+            state: Arc::new(Mutex::new(Snapshot::default())),
+            path: None,
+        }
+    }
+
+    pub fn from_snapshot(path: PathBuf, account_id: i64) -> Result<Self, Box<dyn Error>> {
+        let snapshot = if path.exists() {
+            serde_json::from_reader(std::fs::File::open(&path)?)?
+        } else {
+            Snapshot::default()
+        };
+        Ok(Self {
+            account_id,
+            state: Arc::new(Mutex::new(snapshot)),
+            path: Some(path),
+        })
+    }
+
+    pub fn for_account(&self, account_id: i64) -> Self {
+        Self {
+            account_id,
+            state: self.state.clone(),
+            path: self.path.clone(),
+        }
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        self.state.lock().unwrap().jobs.clone()
+    }
+
+    #[cfg(test)]
+    pub fn add_artifact(&self, run_id: i64, path: &str, contents: &str) {
+        self.state.lock().unwrap().artifacts.push(Artifact {
+            run_id,
+            path: path.to_string(),
+            contents: contents.to_string(),
+        });
+    }
+
+    pub fn runs(&self) -> Vec<Run> {
+        self.state.lock().unwrap().runs.clone()
+    }
+
+    #[cfg(test)]
+    pub fn add_run(&self, run: Run) {
+        self.state.lock().unwrap().runs.push(run);
+    }
+
+    #[cfg(test)]
+    pub fn add_project(&self, project: Project) {
+        self.state.lock().unwrap().projects.push(project);
+    }
+
+    #[cfg(test)]
+    pub fn add_environment(&self, environment: Environment) {
+        self.state.lock().unwrap().environments.push(environment);
+    }
+
+    pub fn notifications(&self) -> Vec<Notification> {
+        self.state.lock().unwrap().notifications.clone()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.path {
+            let f = std::fs::File::create(path)?;
+            serde_json::to_writer_pretty(f, &*self.state.lock().unwrap())?;
+        }
+        Ok(())
+    }
+
+    fn find_index(&self, job: &Job) -> Result<usize, Box<dyn Error>> {
+        let id = job.id.ok_or("id is required")?;
+        self.state
+            .lock()
+            .unwrap()
+            .jobs
+            .iter()
+            .position(|j| j.id == Some(id))
+            .ok_or_else(|| format!("no job with id {}", id).into())
+    }
+
+    fn find_notification_index(
+        &self,
+        notification: &Notification,
+    ) -> Result<usize, Box<dyn Error>> {
+        let id = notification.id.ok_or("id is required")?;
+        self.state
+            .lock()
+            .unwrap()
+            .notifications
+            .iter()
+            .position(|n| n.id == Some(id))
+            .ok_or_else(|| format!("no notification with id {}", id).into())
+    }
+}
+
+pub(crate) fn success<T>(data: T) -> Response<T> {
+    Response {
+        data: Some(data),
+        status: Status {
+            code: 200,
+            is_success: true,
+            user_message: "Success!".to_string(),
+            developer_message: "".to_string(),
+        },
+        extra: None,
+    }
+}
+
+impl DbtCloudApi for FakeDbtCloud {
+    fn get_jobs(&self) -> Result<Response<Vec<Job>>, Box<dyn Error>> {
+        let jobs = self
+            .jobs()
+            .into_iter()
+            .filter(|j| j.account_id == self.account_id)
+            .collect();
+        Ok(success(jobs))
+    }
+
+    fn create_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+        let mut created = job.clone();
+        {
+            let mut state = self.state.lock().unwrap();
+            let next_id = state.jobs.iter().filter_map(|j| j.id).max().unwrap_or(0) + 1;
+            created.id = Some(next_id);
+            state.jobs.push(created.clone());
+        }
+        self.save()?;
+        Ok(success(created))
+    }
+
+    fn update_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+        let index = self.find_index(job)?;
+        self.state.lock().unwrap().jobs[index] = job.clone();
+        self.save()?;
+        Ok(success(job.clone()))
+    }
+
+    fn delete_job(&self, job: &Job) -> Result<Response<Job>, Box<dyn Error>> {
+        let index = self.find_index(job)?;
+        let deleted = self.state.lock().unwrap().jobs.remove(index);
+        self.save()?;
+        Ok(success(deleted))
+    }
+
+    /// Runs finish instantly and successfully
+    fn trigger_run(
+        &self,
+        job_id: i64,
+        trigger: &TriggerRun,
+    ) -> Result<Response<Run>, Box<dyn Error>> {
+        let job = self
+            .jobs()
+            .into_iter()
+            .find(|j| j.id == Some(job_id))
+            .ok_or_else(|| format!("no job with id {}", job_id))?;
+        let steps = trigger
+            .steps_override
+            .clone()
+            .unwrap_or_else(|| job.execute_steps.clone());
+        let run = {
+            let mut state = self.state.lock().unwrap();
+            let run = Run {
+                id: state.runs.iter().map(|r| r.id).max().unwrap_or(0) + 1,
+                account_id: job.account_id,
+                project_id: job.project_id,
+                job_definition_id: job_id,
+                status: Run::SUCCESS,
+                status_humanized: "Success".to_string(),
+                git_branch: trigger.git_branch.clone(),
+                trigger: Some(RunTrigger {
+                    cause: trigger.cause.clone(),
+                }),
+                run_steps: steps
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, name)| RunStep {
+                        id: i as i64 + 1,
+                        index: i as i64 + 1,
+                        name,
+                        status: Run::SUCCESS,
+                        status_humanized: "Success".to_string(),
+                        duration_humanized: None,
+                    })
+                    .collect(),
+                ..Run::default()
+            };
+            state.runs.push(run.clone());
+            run
+        };
+        self.save()?;
+        Ok(success(run))
+    }
+
+    fn get_runs(
+        &self,
+        job_id: i64,
+        status: Option<i64>,
+        limit: usize,
+    ) -> Result<Response<Vec<Run>>, Box<dyn Error>> {
+        let mut runs: Vec<_> = self
+            .runs()
+            .into_iter()
+            .filter(|r| r.job_definition_id == job_id)
+            .filter(|r| status.is_none_or(|s| s == r.status))
+            .collect();
+        runs.sort_by_key(|r| std::cmp::Reverse(r.id));
+        runs.truncate(limit);
+        Ok(success(runs))
+    }
+
+    fn get_run_artifact(&self, run_id: i64, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let state = self.state.lock().unwrap();
+        let artifact = state
+            .artifacts
+            .iter()
+            .find(|a| a.run_id == run_id && a.path == path)
+            .ok_or_else(|| format!("run {} has no {}", run_id, path))?;
+        Ok(artifact.contents.as_bytes().to_vec())
+    }
+
+    fn get_run(&self, run_id: i64) -> Result<Response<Run>, Box<dyn Error>> {
+        let run = self
+            .runs()
+            .into_iter()
+            .find(|r| r.id == run_id)
+            .ok_or_else(|| format!("no run with id {}", run_id))?;
+        Ok(success(run))
+    }
+
+    fn get_notifications(&self) -> Result<Response<Vec<Notification>>, Box<dyn Error>> {
+        let notifications = self
+            .notifications()
+            .into_iter()
+            .filter(|n| n.account_id == self.account_id)
+            .collect();
+        Ok(success(notifications))
+    }
+
+    fn create_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>> {
+        let mut created = notification.clone();
+        {
+            let mut state = self.state.lock().unwrap();
+            let next_id = state
+                .notifications
+                .iter()
+                .filter_map(|n| n.id)
+                .max()
+                .unwrap_or(0)
+                + 1;
+            created.id = Some(next_id);
+            state.notifications.push(created.clone());
+        }
+        self.save()?;
+        Ok(success(created))
+    }
+
+    fn update_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>> {
+        let index = self.find_notification_index(notification)?;
+        self.state.lock().unwrap().notifications[index] = notification.clone();
+        self.save()?;
+        Ok(success(notification.clone()))
+    }
+
+    fn delete_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Response<Notification>, Box<dyn Error>> {
+        let index = self.find_notification_index(notification)?;
+        let deleted = self.state.lock().unwrap().notifications.remove(index);
+        self.save()?;
+        Ok(success(deleted))
+    }
+
+    fn get_dbt_versions(&self) -> Result<Response<Vec<DbtVersion>>, Box<dyn Error>> {
+        let mut versions = self.state.lock().unwrap().dbt_versions.clone();
+        if versions.is_empty() {
+            versions = DBT_VERSIONS
+                .iter()
+                .map(|name| DbtVersion {
+                    name: name.to_string(),
+                    description: None,
+                })
+                .collect();
+        }
+        Ok(success(versions))
+    }
+
+    fn get_environment(
+        &self,
+        project_id: i64,
+        environment_id: i64,
+    ) -> Result<Response<Environment>, Box<dyn Error>> {
+        let environment = self
+            .state
+            .lock()
+            .unwrap()
+            .environments
+            .iter()
+            .find(|e| e.id == Some(environment_id) && e.project_id == project_id)
+            .cloned()
+            .unwrap_or(Environment {
+                id: Some(environment_id),
+                account_id: self.account_id,
+                project_id,
+                ..Environment::default()
+            });
+        Ok(success(environment))
+    }
+
+    fn get_projects(&self) -> Result<Response<Vec<Project>>, Box<dyn Error>> {
+        let mut projects = self.state.lock().unwrap().projects.clone();
+        projects.retain(|p| p.account_id == self.account_id);
+        Ok(success(projects))
+    }
+
+    /// Only the environments in the snapshot, unlike `get_environment`
+    fn get_environments(&self) -> Result<Response<Vec<Environment>>, Box<dyn Error>> {
+        let mut environments = self.state.lock().unwrap().environments.clone();
+        environments.retain(|e| e.account_id == self.account_id);
+        Ok(success(environments))
+    }
+
+    fn get_account(&self) -> Result<Response<Account>, Box<dyn Error>> {
+        let account = self
+            .state
+            .lock()
+            .unwrap()
+            .accounts
+            .iter()
+            .find(|a| a.id == self.account_id)
+            .cloned()
+            .unwrap_or(Account {
+                id: self.account_id,
+                name: String::new(),
+                run_slots: RUN_SLOTS,
+            });
+        Ok(success(account))
+    }
+}
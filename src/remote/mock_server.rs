@@ -0,0 +1,630 @@
+// A tiny stand-in for the dbt Cloud API, good enough to point `DBT_CLOUD_BASE_URL` at.
+// It only speaks the subset of HTTP/1.1 that reqwest uses against it and serves one connection at a time.
+
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::remote::api::DbtCloudApi;
+use crate::remote::fake::{success, FakeDbtCloud};
+use crate::remote::types::{Extra, Job, Notification, Pagination, Response, Status, TriggerRun};
+
+const MAX_PAGE_SIZE: usize = 100;
+
+/// A failure to answer with instead of handling the next request normally
+#[derive(Debug, Clone, Copy)]
+pub struct Failure {
+    pub status: u16,
+    /// Handle the request anyway before failing, like a gateway timing out on a slow create
+    pub after_handling: bool,
+}
+
+pub struct MockServer {
+    listener: TcpListener,
+    api: FakeDbtCloud,
+    account_id: i64,
+    token: Option<String>,
+    failures: Arc<Mutex<VecDeque<Failure>>>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Reply {
+    status: u16,
+    body: String,
+}
+
+impl MockServer {
+    /// Binds to `addr`; when `token` is `None` any `Token ...` authorization is accepted
+    pub fn bind(
+        addr: &str,
+        account_id: i64,
+        token: Option<String>,
+        api: FakeDbtCloud,
+    ) -> std::io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            api,
+            account_id,
+            token,
+            failures: Arc::new(Mutex::new(VecDeque::new())),
+        })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.listener.local_addr().unwrap())
+    }
+
+    /// Queue of failures consumed in order by the next requests, shareable with other threads
+    #[cfg(test)]
+    pub fn failures(&self) -> Arc<Mutex<VecDeque<Failure>>> {
+        self.failures.clone()
+    }
+
+    pub fn serve(self) {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            if let Err(err) = self.handle_connection(stream) {
+                eprintln!("mock server failed to handle request: {}", err);
+            }
+        }
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
+        let request = read_request(&mut stream)?;
+        let failure = self.failures.lock().unwrap().pop_front();
+        let reply = match failure {
+            Some(f) if !f.after_handling => error(f.status, "Injected failure."),
+            Some(f) => {
+                self.route(&request);
+                error(f.status, "Injected failure.")
+            }
+            None => self.route(&request),
+        };
+        write_reply(&mut stream, &reply)?;
+        Ok(())
+    }
+
+    fn route(&self, request: &Request) -> Reply {
+        if !self.authorized(request) {
+            return error(401, "Invalid token.");
+        }
+        let segments: Vec<_> = request.path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            ["api", "v3", "accounts", account, "projects", project, "environments", id] => {
+                if account.parse::<i64>().ok() != Some(self.account_id) {
+                    return error(404, "Account not found.");
+                }
+                match (request.method.as_str(), project.parse(), id.parse()) {
+                    ("GET", Ok(project), Ok(id)) => {
+                        reply(200, &self.api.get_environment(project, id).unwrap())
+                    }
+                    _ => error(404, "Not found."),
+                }
+            }
+            ["api", "v2", "accounts", account, rest @ ..] => {
+                if account.parse::<i64>().ok() != Some(self.account_id) {
+                    return error(404, "Account not found.");
+                }
+                match (request.method.as_str(), rest) {
+                    ("GET", []) => reply(200, &self.api.get_account().unwrap()),
+                    ("GET", ["jobs"]) => self.list_jobs(request),
+                    ("POST", ["jobs"]) => self.create_job(request),
+                    (method, ["jobs", id]) => match id.parse::<i64>() {
+                        Ok(id) => self.job(method, id, request),
+                        Err(_) => error(404, "Not found."),
+                    },
+                    ("POST", ["jobs", id, "run"]) => match id.parse::<i64>() {
+                        Ok(id) => self.trigger_run(id, request),
+                        Err(_) => error(404, "Not found."),
+                    },
+                    ("GET", ["runs"]) => self.list_runs(request),
+                    ("GET", ["runs", id, "artifacts", path @ ..]) => {
+                        let artifact = id
+                            .parse::<i64>()
+                            .ok()
+                            .and_then(|id| self.api.get_run_artifact(id, &path.join("/")).ok());
+                        match artifact {
+                            Some(contents) => Reply {
+                                status: 200,
+                                body: String::from_utf8_lossy(&contents).to_string(),
+                            },
+                            None => error(404, "Artifact not found."),
+                        }
+                    }
+                    ("GET", ["runs", id]) => match id.parse::<i64>() {
+                        Ok(id) => match self.api.get_run(id) {
+                            Ok(run) => reply(200, &run),
+                            Err(_) => error(404, "Run not found."),
+                        },
+                        Err(_) => error(404, "Not found."),
+                    },
+                    ("GET", ["versions"]) => reply(200, &self.api.get_dbt_versions().unwrap()),
+                    ("GET", ["projects"]) => {
+                        paginate(request, self.api.get_projects().unwrap().data)
+                    }
+                    ("GET", ["environments"]) => {
+                        paginate(request, self.api.get_environments().unwrap().data)
+                    }
+                    ("GET", ["notifications"]) => {
+                        paginate(request, self.api.get_notifications().unwrap().data)
+                    }
+                    ("POST", ["notifications"]) => self.create_notification(request),
+                    (method, ["notifications", id]) => match id.parse::<i64>() {
+                        Ok(id) => self.notification(method, id, request),
+                        Err(_) => error(404, "Not found."),
+                    },
+                    _ => error(404, "Not found."),
+                }
+            }
+            _ => error(404, "Not found."),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        match request.headers.get("authorization") {
+            Some(value) => match (value.strip_prefix("Token "), &self.token) {
+                (Some(given), Some(expected)) => given == expected,
+                (Some(given), None) => !given.is_empty(),
+                (None, _) => false,
+            },
+            None => false,
+        }
+    }
+
+    fn list_jobs(&self, request: &Request) -> Reply {
+        let project_id = query_number(request, "project_id").map(|p| p as i64);
+        let mut jobs = self.api.get_jobs().unwrap().data.unwrap_or_default();
+        if let Some(project_id) = project_id {
+            jobs.retain(|j| j.project_id == project_id);
+        }
+        paginate(request, Some(jobs))
+    }
+
+    fn create_job(&self, request: &Request) -> Reply {
+        let job: Job = match serde_json::from_slice(&request.body) {
+            Ok(j) => j,
+            Err(err) => return error(400, &format!("Invalid job: {}", err)),
+        };
+        match self.api.create_job(&job) {
+            Ok(created) => reply(201, &created),
+            Err(err) => error(400, &err.to_string()),
+        }
+    }
+
+    fn list_runs(&self, request: &Request) -> Reply {
+        let job_id = match query_number(request, "job_definition_id") {
+            Some(id) => id as i64,
+            None => return error(400, "job_definition_id is required."),
+        };
+        let offset = query_number(request, "offset").unwrap_or(0);
+        let limit = query_number(request, "limit")
+            .unwrap_or(MAX_PAGE_SIZE)
+            .min(MAX_PAGE_SIZE);
+        let status = query_number(request, "status").map(|s| s as i64);
+        let runs = self.api.get_runs(job_id, status, offset + limit).unwrap();
+        let page: Vec<_> = runs.data.into_iter().flatten().skip(offset).collect();
+        reply(200, &success(page))
+    }
+
+    fn trigger_run(&self, job_id: i64, request: &Request) -> Reply {
+        let trigger: TriggerRun = match serde_json::from_slice(&request.body) {
+            Ok(t) => t,
+            Err(err) => return error(400, &format!("Invalid run: {}", err)),
+        };
+        match self.api.trigger_run(job_id, &trigger) {
+            Ok(run) => reply(200, &run),
+            Err(_) => error(404, "Job not found."),
+        }
+    }
+
+    fn job(&self, method: &str, id: i64, request: &Request) -> Reply {
+        let existing = self.api.jobs().into_iter().find(|j| j.id == Some(id));
+        let existing = match existing {
+            Some(j) => j,
+            None => return error(404, "Job not found."),
+        };
+        match method {
+            "GET" => reply(200, &success(existing)),
+            "POST" => {
+                let mut job: Job = match serde_json::from_slice(&request.body) {
+                    Ok(j) => j,
+                    Err(err) => return error(400, &format!("Invalid job: {}", err)),
+                };
+                job.id = Some(id);
+                match self.api.update_job(&job) {
+                    Ok(updated) => reply(200, &updated),
+                    Err(err) => error(400, &err.to_string()),
+                }
+            }
+            "DELETE" => match self.api.delete_job(&existing) {
+                Ok(deleted) => reply(200, &deleted),
+                Err(err) => error(400, &err.to_string()),
+            },
+            _ => error(405, "Method not allowed."),
+        }
+    }
+
+    fn create_notification(&self, request: &Request) -> Reply {
+        let notification: Notification = match serde_json::from_slice(&request.body) {
+            Ok(n) => n,
+            Err(err) => return error(400, &format!("Invalid notification: {}", err)),
+        };
+        match self.api.create_notification(&notification) {
+            Ok(created) => reply(201, &created),
+            Err(err) => error(400, &err.to_string()),
+        }
+    }
+
+    fn notification(&self, method: &str, id: i64, request: &Request) -> Reply {
+        let existing = self
+            .api
+            .notifications()
+            .into_iter()
+            .find(|n| n.id == Some(id) && n.account_id == self.account_id);
+        let existing = match existing {
+            Some(n) => n,
+            None => return error(404, "Notification not found."),
+        };
+        match method {
+            "GET" => reply(200, &success(existing)),
+            "POST" => {
+                let mut notification: Notification = match serde_json::from_slice(&request.body) {
+                    Ok(n) => n,
+                    Err(err) => return error(400, &format!("Invalid notification: {}", err)),
+                };
+                notification.id = Some(id);
+                match self.api.update_notification(&notification) {
+                    Ok(updated) => reply(200, &updated),
+                    Err(err) => error(400, &err.to_string()),
+                }
+            }
+            "DELETE" => match self.api.delete_notification(&existing) {
+                Ok(deleted) => reply(200, &deleted),
+                Err(err) => error(400, &err.to_string()),
+            },
+            _ => error(405, "Method not allowed."),
+        }
+    }
+}
+
+fn query_number(request: &Request, key: &str) -> Option<usize> {
+    request.query.get(key).and_then(|v| v.parse().ok())
+}
+
+/// Answers a list request with the page picked by `offset` and `limit`, like dbt Cloud does
+fn paginate<T: Serialize>(request: &Request, items: Option<Vec<T>>) -> Reply {
+    let offset = query_number(request, "offset").unwrap_or(0);
+    let limit = query_number(request, "limit")
+        .unwrap_or(MAX_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE);
+    let items = items.unwrap_or_default();
+    let total_count = items.len();
+    let page: Vec<_> = items.into_iter().skip(offset).take(limit).collect();
+    let response = Response {
+        extra: Some(Extra {
+            pagination: Some(Pagination {
+                count: page.len(),
+                total_count,
+            }),
+        }),
+        ..success(page)
+    };
+    reply(200, &response)
+}
+
+fn reply<T: Serialize>(status: u16, body: &T) -> Reply {
+    Reply {
+        status,
+        body: serde_json::to_string(body).unwrap(),
+    }
+}
+
+/// Errors look just like dbt Cloud's: the usual envelope with `is_success: false` and no data
+fn error(status: u16, message: &str) -> Reply {
+    let body = json!({
+        "status": Status {
+            code: status as i64,
+            is_success: false,
+            user_message: message.to_string(),
+            developer_message: "".to_string(),
+        },
+        "data": null,
+    });
+    reply(status, &body)
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, Box<dyn Error>> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("missing method")?.to_string();
+    let target = parts.next().ok_or("missing path")?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = header.split_once(':') {
+            headers.insert(k.trim().to_ascii_lowercase(), v.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+fn write_reply(stream: &mut TcpStream, reply: &Reply) -> std::io::Result<()> {
+    let reason = match reply.status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        reply.status,
+        reason,
+        reply.body.len(),
+        reply.body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Failure, MockServer};
+    use crate::{
+        config::Config,
+        remote::{
+            DbtCloudApi, DbtCloudClient, FakeDbtCloud, Job, Notification, RetryPolicy, TriggerRun,
+        },
+    };
+
+    const ACCOUNT_ID: i64 = 123;
+
+    fn start(api: FakeDbtCloud) -> (Config, Arc<Mutex<VecDeque<Failure>>>) {
+        let server =
+            MockServer::bind("127.0.0.1:0", ACCOUNT_ID, Some("secret".to_string()), api).unwrap();
+        let config = Config {
+            account_id: ACCOUNT_ID,
+            token: "secret".to_string(),
+            project_id: None,
+            base_url: server.base_url(),
+            retry: RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+            },
+        };
+        let failures = server.failures();
+        thread::spawn(move || server.serve());
+        (config, failures)
+    }
+
+    fn job(name: &str) -> Job {
+        Job {
+            account_id: ACCOUNT_ID,
+            project_id: 456,
+            name: name.to_string(),
+            ..Job::default()
+        }
+    }
+
+    #[test]
+    fn create_update_delete() {
+        let (config, _) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let client = DbtCloudClient::new(&config);
+
+        let created = client.create_job(&job("Seed")).unwrap().data.unwrap();
+        assert!(created.id.is_some());
+
+        let mut changed = created.clone();
+        changed.execute_steps = vec!["dbt seed".to_string()];
+        client.update_job(&changed).unwrap();
+        let jobs = client.get_jobs_for_project(456).unwrap().data.unwrap();
+        assert_eq!(jobs, vec![changed.clone()]);
+
+        client.delete_job(&changed).unwrap();
+        assert!(client.get_jobs().unwrap().data.unwrap().is_empty());
+    }
+
+    #[test]
+    fn notifications() {
+        let (config, _) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let client = DbtCloudClient::new(&config);
+
+        let notification = Notification {
+            account_id: ACCOUNT_ID,
+            notification_type: Notification::EXTERNAL_EMAIL,
+            external_email: Some("oncall@example.com".to_string()),
+            on_failure: vec![1],
+            ..Notification::default()
+        };
+        let mut created = client
+            .create_notification(&notification)
+            .unwrap()
+            .data
+            .unwrap();
+        created.on_cancel = vec![1];
+        client.update_notification(&created).unwrap();
+        let listed = client.get_notifications().unwrap().data.unwrap();
+        assert_eq!(listed, vec![created.clone()]);
+
+        client.delete_notification(&created).unwrap();
+        assert!(client.get_notifications().unwrap().data.unwrap().is_empty());
+    }
+
+    #[test]
+    fn lists_every_page() {
+        let api = FakeDbtCloud::new(ACCOUNT_ID);
+        for i in 0..250 {
+            api.create_job(&job(&format!("Job {}", i))).unwrap();
+        }
+        let (config, _) = start(api);
+        let client = DbtCloudClient::new(&config);
+        assert_eq!(client.get_jobs().unwrap().data.unwrap().len(), 250);
+    }
+
+    #[test]
+    fn lists_every_page_of_notifications() {
+        let api = FakeDbtCloud::new(ACCOUNT_ID);
+        for i in 0..150 {
+            let notification = Notification {
+                account_id: ACCOUNT_ID,
+                notification_type: Notification::EXTERNAL_EMAIL,
+                external_email: Some(format!("oncall{}@example.com", i)),
+                on_failure: vec![1],
+                ..Notification::default()
+            };
+            api.create_notification(&notification).unwrap();
+        }
+        let (config, _) = start(api);
+        let client = DbtCloudClient::new(&config);
+        assert_eq!(client.get_notifications().unwrap().data.unwrap().len(), 150);
+    }
+
+    #[test]
+    fn lists_runs_past_one_page() {
+        let api = FakeDbtCloud::new(ACCOUNT_ID);
+        let created = api.create_job(&job("Seed")).unwrap().data.unwrap();
+        let job_id = created.id.unwrap();
+        for _ in 0..150 {
+            api.trigger_run(job_id, &TriggerRun::default()).unwrap();
+        }
+        let (config, _) = start(api);
+        let client = DbtCloudClient::new(&config);
+        let runs = client.get_runs(job_id, None, 120).unwrap().data.unwrap();
+        assert_eq!(runs.len(), 120);
+        assert!(runs.windows(2).all(|w| w[0].id > w[1].id), "newest first");
+        let all = client.get_runs(job_id, None, 500).unwrap().data.unwrap();
+        assert_eq!(all.len(), 150);
+    }
+
+    #[test]
+    fn wrong_token_is_rejected() {
+        let (config, _) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let config = Config {
+            token: "wrong".to_string(),
+            ..config
+        };
+        let client = DbtCloudClient::new(&config);
+        let err = client.get_jobs().unwrap_err();
+        let err = err.downcast_ref::<reqwest::Error>().unwrap();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn missing_job_is_an_error() {
+        let (config, _) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let client = DbtCloudClient::new(&config);
+        let missing = Job {
+            id: Some(999),
+            ..job("Missing")
+        };
+        let err = client.update_job(&missing).unwrap_err();
+        let err = err.downcast_ref::<reqwest::Error>().unwrap();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let (config, failures) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let client = DbtCloudClient::new(&config);
+        failures.lock().unwrap().extend([
+            Failure {
+                status: 503,
+                after_handling: false,
+            },
+            Failure {
+                status: 429,
+                after_handling: false,
+            },
+        ]);
+        assert!(client.get_jobs().unwrap().data.unwrap().is_empty());
+
+        failures.lock().unwrap().extend(
+            [Failure {
+                status: 503,
+                after_handling: false,
+            }; 3],
+        );
+        assert!(client.get_jobs().is_err(), "gives up after max_retries");
+    }
+
+    #[test]
+    fn retried_create_is_not_duplicated() {
+        let (config, failures) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let client = DbtCloudClient::new(&config);
+        failures.lock().unwrap().push_back(Failure {
+            status: 502,
+            after_handling: true,
+        });
+        let created = client.create_job(&job("Seed")).unwrap().data.unwrap();
+        assert_eq!(created.name, "Seed");
+        assert_eq!(client.get_jobs().unwrap().data.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retried_delete_finds_it_gone() {
+        let (config, failures) = start(FakeDbtCloud::new(ACCOUNT_ID));
+        let client = DbtCloudClient::new(&config);
+        let created = client.create_job(&job("Seed")).unwrap().data.unwrap();
+        failures.lock().unwrap().push_back(Failure {
+            status: 502,
+            after_handling: true,
+        });
+        client.delete_job(&created).unwrap();
+        assert!(client.get_jobs().unwrap().data.unwrap().is_empty());
+    }
+}
@@ -1,5 +1,13 @@
+mod api;
 mod client;
+mod fake;
+mod mock_server;
+mod retry;
 mod types;
 
+pub use api::DbtCloudApi;
 pub use client::DbtCloudClient;
+pub use fake::FakeDbtCloud;
+pub use mock_server::MockServer;
+pub use retry::RetryPolicy;
 pub use types::*;
@@ -0,0 +1,148 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest::blocking::Response;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+
+const MAX_RETRIES_ENV: &str = "DBTERRA_MAX_RETRIES";
+const BASE_DELAY_ENV: &str = "DBTERRA_RETRY_BASE_DELAY_MS";
+const MAX_DELAY_ENV: &str = "DBTERRA_RETRY_MAX_DELAY_MS";
+
+/// How many times (and how patiently) transient failures are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Result<Self, String> {
+        let default = RetryPolicy::default();
+        Ok(RetryPolicy {
+            max_retries: read_env(MAX_RETRIES_ENV)?.unwrap_or(default.max_retries),
+            base_delay: read_env(BASE_DELAY_ENV)?
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+            max_delay: read_env(MAX_DELAY_ENV)?
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_delay),
+        })
+    }
+
+    /// Returns how long to wait before retrying, or `None` if the result should be returned as is
+    pub fn should_retry(
+        &self,
+        attempt: u32,
+        result: &reqwest::Result<Response>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        match result {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => Some(
+                response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after)
+                    .unwrap_or_else(|| self.backoff(attempt)),
+            ),
+            Ok(response) if is_transient(response.status()) => Some(self.backoff(attempt)),
+            Ok(_) => None,
+            Err(err) if err.is_connect() || err.is_timeout() => Some(self.backoff(attempt)),
+            Err(_) => None,
+        }
+    }
+
+    /// Exponential backoff with "equal jitter": half of the delay is fixed, the other half random
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        let jitter = match half.as_millis() as u64 {
+            0 => 0,
+            millis => random() % (millis + 1),
+        };
+        half + Duration::from_millis(jitter)
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Only the delta-seconds form is supported, dbt Cloud doesn't send HTTP dates
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+fn read_env<T: std::str::FromStr>(key: &str) -> Result<Option<T>, String> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} must be a number", key)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Good enough randomness for jitter without pulling in another dependency
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_retry_after, RetryPolicy};
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+        for attempt in 0..10 {
+            let expected = (100 * 2u64.pow(attempt)).min(1000);
+            let delay = policy.backoff(attempt).as_millis() as u64;
+            assert!(
+                delay >= expected / 2,
+                "attempt {} waited {}ms",
+                attempt,
+                delay
+            );
+            assert!(delay <= expected, "attempt {} waited {}ms", attempt, delay);
+        }
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::from_secs(0)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
@@ -1,9 +1,24 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Response<T> {
     pub data: Option<T>,
     pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Extra>,
+}
+
+/// Only sent for list endpoints
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Extra {
+    pub pagination: Option<Pagination>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pagination {
+    pub count: usize,
+    pub total_count: usize,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -23,6 +38,48 @@ pub struct Job {
     pub deferring_environment_id: Option<i64>,
     pub schedule: Schedule,
     pub execution: Execution,
+    #[serde(default)]
+    pub job_completion_trigger_condition: Option<JobCompletionTrigger>,
+    /// `ci`, `merge`, `scheduled` or `other`
+    #[serde(default = "Job::default_job_type")]
+    pub job_type: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub run_generate_sources: bool,
+    #[serde(default)]
+    pub triggers_on_draft_pr: bool,
+    #[serde(default)]
+    pub run_compare_changes: bool,
+    #[serde(default = "Job::default_compare_changes_flags")]
+    pub compare_changes_flags: String,
+    #[serde(default)]
+    pub run_lint: bool,
+    #[serde(default = "Job::default_errors_on_lint_failure")]
+    pub errors_on_lint_failure: bool,
+    /// Computed by dbt Cloud, never sent back
+    #[serde(default, skip_serializing)]
+    pub next_run: Option<String>,
+    /// Settings dbterra doesn't know about, sent back untouched so updates don't reset them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// What dbt Cloud assumes when a job doesn't say
+impl Job {
+    pub const COMPARE_CHANGES_FLAGS: &'static str = "--select state:modified";
+
+    fn default_job_type() -> String {
+        "other".to_string()
+    }
+
+    fn default_compare_changes_flags() -> String {
+        Job::COMPARE_CHANGES_FLAGS.to_string()
+    }
+
+    fn default_errors_on_lint_failure() -> bool {
+        true
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -33,6 +90,21 @@ pub struct Triggers {
     pub custom_branch_only: Option<bool>,
 }
 
+/// Runs the job whenever another job finishes with one of `statuses`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobCompletionTrigger {
+    pub condition: JobCompletionCondition,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobCompletionCondition {
+    /// `None` until the other job has been created, see `Plan::apply`
+    pub job_id: Option<i64>,
+    pub project_id: i64,
+    /// Run statuses like `Run::SUCCESS`
+    pub statuses: Vec<i64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Settings {
     pub threads: i64,
@@ -105,3 +177,129 @@ pub struct Status {
     pub user_message: String,
     pub developer_message: String,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Run {
+    pub id: i64,
+    pub account_id: i64,
+    pub project_id: i64,
+    pub job_definition_id: i64,
+    pub status: i64,
+    pub status_humanized: String,
+    pub git_branch: Option<String>,
+    pub created_at: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub duration_humanized: Option<String>,
+    pub href: Option<String>,
+    pub trigger: Option<RunTrigger>,
+    pub run_steps: Vec<RunStep>,
+}
+
+impl Run {
+    pub const SUCCESS: i64 = 10;
+    pub const ERROR: i64 = 20;
+    pub const CANCELLED: i64 = 30;
+
+    pub fn is_complete(&self) -> bool {
+        matches!(self.status, Run::SUCCESS | Run::ERROR | Run::CANCELLED)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == Run::SUCCESS
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunTrigger {
+    pub cause: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStep {
+    pub id: i64,
+    pub index: i64,
+    pub name: String,
+    pub status: i64,
+    pub status_humanized: String,
+    pub duration_humanized: Option<String>,
+}
+
+/// Body for kicking off a job run, unset values fall back to the job's configuration
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriggerRun {
+    pub cause: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps_override: Option<Vec<String>>,
+}
+
+/// Who gets told about which jobs finishing. A single recipient covers any number of jobs,
+/// one list of job ids per event.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notification {
+    pub id: Option<i64>,
+    pub account_id: i64,
+    /// Required by dbt Cloud for some notification types, kept as is when updating
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i64>,
+    pub notification_type: i64,
+    pub state: i64,
+    pub on_success: Vec<i64>,
+    pub on_failure: Vec<i64>,
+    pub on_cancel: Vec<i64>,
+    pub on_warning: Vec<i64>,
+    pub external_email: Option<String>,
+    pub slack_channel_id: Option<String>,
+    pub slack_channel_name: Option<String>,
+}
+
+impl Notification {
+    pub const SLACK: i64 = 2;
+    pub const EXTERNAL_EMAIL: i64 = 4;
+}
+
+/// A dbt version jobs and environments can be pinned to
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbtVersion {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Account {
+    pub id: i64,
+    pub name: String,
+    /// How many runs can go at once, the rest wait in a queue
+    pub run_slots: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub id: Option<i64>,
+    pub account_id: i64,
+    pub name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Environment {
+    pub id: Option<i64>,
+    pub account_id: i64,
+    pub project_id: i64,
+    pub name: String,
+    pub dbt_version: Option<String>,
+    /// `deployment` or `development`
+    #[serde(rename = "type")]
+    pub type_field: Option<String>,
+    /// `production`, `staging` or `None` for general deployment environments
+    pub deployment_type: Option<String>,
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use crate::{local::Root, remote::DbtCloudApi};
+
+/// A project or environment id dbt_cloud.yml left out and that was looked up by its name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub kind: &'static str,
+    pub key: String,
+    pub name: String,
+    pub id: i64,
+}
+
+/// Fills in the ids of projects and environments that only have a `name`. dbt Cloud is only
+/// asked when there is something to look up.
+pub fn ids(yaml: &mut Root, client: &dyn DbtCloudApi) -> Result<Vec<Resolved>, String> {
+    let mut project_keys: Vec<_> = yaml
+        .projects
+        .iter()
+        .filter(|(_, p)| p.id.is_none())
+        .map(|(k, p)| p.name.as_ref().map(|_| k.to_string()).ok_or(k))
+        .collect::<Result<_, _>>()
+        .map_err(|k| format!("project `{}` needs an `id` or a `name`", k))?;
+    let mut environment_keys: Vec<_> = yaml
+        .environments
+        .iter()
+        .filter(|(_, e)| e.id.is_none())
+        .map(|(k, e)| e.name.as_ref().map(|_| k.to_string()).ok_or(k))
+        .collect::<Result<_, _>>()
+        .map_err(|k| format!("environment `{}` needs an `id` or a `name`", k))?;
+    project_keys.sort();
+    environment_keys.sort();
+
+    let mut resolved = vec![];
+    if !project_keys.is_empty() {
+        let remote = client
+            .get_projects()
+            .map_err(|err| format!("failed to get projects: {}", err))?
+            .data
+            .unwrap_or_default();
+        for key in project_keys {
+            let project = yaml.projects.get_mut(&key).unwrap();
+            let name = project.name.clone().unwrap_or_default();
+            let matches = remote
+                .iter()
+                .filter(|p| p.name == name)
+                .filter_map(|p| p.id);
+            let id = only_one(matches.collect(), "projects", &name, "set `id` to pick one")?;
+            project.id = Some(id);
+            resolved.push(Resolved {
+                kind: "project",
+                key,
+                name,
+                id,
+            });
+        }
+    }
+
+    if !environment_keys.is_empty() {
+        let remote = client
+            .get_environments()
+            .map_err(|err| format!("failed to get environments: {}", err))?
+            .data
+            .unwrap_or_default();
+        let project_ids: HashMap<_, _> = yaml
+            .projects
+            .iter()
+            .map(|(k, p)| (k.to_string(), p.id))
+            .collect();
+        for key in environment_keys {
+            let environment = yaml.environments.get_mut(&key).unwrap();
+            let name = environment.name.clone().unwrap_or_default();
+            let project_id = environment
+                .project
+                .as_ref()
+                .map(|project| {
+                    project_ids.get(project).copied().flatten().ok_or_else(|| {
+                        format!(
+                            "environment `{}` is in project `{}` but there's no such project",
+                            key, project
+                        )
+                    })
+                })
+                .transpose()?;
+            let matches = remote
+                .iter()
+                .filter(|e| e.name == name && project_id.is_none_or(|p| p == e.project_id))
+                .filter_map(|e| e.id);
+            let hint = "set `id` or `project` to pick one";
+            let id = only_one(matches.collect(), "environments", &name, hint)?;
+            environment.id = Some(id);
+            resolved.push(Resolved {
+                kind: "environment",
+                key,
+                name,
+                id,
+            });
+        }
+    }
+    Ok(resolved)
+}
+
+fn only_one(mut ids: Vec<i64>, kind: &str, name: &str, hint: &str) -> Result<i64, String> {
+    ids.sort();
+    match ids.as_slice() {
+        [id] => Ok(*id),
+        [] => Err(format!("no {} named `{}` in dbt Cloud", kind, name)),
+        _ => {
+            let ids: Vec<_> = ids.iter().map(|id| id.to_string()).collect();
+            Err(format!(
+                "several {} are named `{}` ({}), {}",
+                kind,
+                name,
+                ids.join(", "),
+                hint
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ids, Resolved};
+    use crate::{
+        local::Root,
+        remote::{Environment, FakeDbtCloud, Project},
+    };
+
+    fn root(environment: &str) -> Root {
+        serde_yaml::from_str(&format!(
+            "
+projects:
+  analytics:
+    name: Analytics
+    jobs: {{}}
+  marketing:
+    id: 2
+    jobs: {{}}
+environments:
+  prod:
+    {}
+",
+            environment
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn looks_up_names() {
+        let fake = FakeDbtCloud::new(123);
+        for (id, name) in [(1, "Analytics"), (2, "Marketing")] {
+            fake.add_project(Project {
+                id: Some(id),
+                account_id: 123,
+                name: name.to_string(),
+            });
+            fake.add_environment(Environment {
+                id: Some(id + 10),
+                account_id: 123,
+                project_id: id,
+                name: "Production".to_string(),
+                ..Environment::default()
+            });
+        }
+
+        let mut yaml = root("{name: Production, project: analytics}");
+        let resolved = ids(&mut yaml, &fake).unwrap();
+        assert_eq!(yaml.projects["analytics"].id, Some(1));
+        assert_eq!(yaml.environments["prod"].id, Some(11));
+        assert_eq!(
+            resolved[0],
+            Resolved {
+                kind: "project",
+                key: "analytics".to_string(),
+                name: "Analytics".to_string(),
+                id: 1,
+            }
+        );
+
+        let err = ids(&mut root("{name: Production}"), &fake).unwrap_err();
+        assert_eq!(
+            err,
+            "several environments are named `Production` (11, 12), set `id` or `project` to pick one"
+        );
+        let err = ids(&mut root("{name: Staging}"), &fake).unwrap_err();
+        assert_eq!(err, "no environments named `Staging` in dbt Cloud");
+        let err = ids(&mut root("{project: analytics}"), &fake).unwrap_err();
+        assert_eq!(err, "environment `prod` needs an `id` or a `name`");
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::thread::sleep;
+use std::time::Duration;
+
+use colored::Colorize;
+
+use crate::remote::{DbtCloudApi, Run, RunStep, TriggerRun};
+
+/// Triggers a run of `job_id`, returning it as soon as dbt Cloud accepted it
+pub fn trigger(
+    api: &dyn DbtCloudApi,
+    job_id: i64,
+    trigger: &TriggerRun,
+) -> Result<Run, Box<dyn Error>> {
+    let run = api
+        .trigger_run(job_id, trigger)?
+        .data
+        .ok_or("dbt Cloud didn't return the triggered run")?;
+    match &run.href {
+        Some(href) => println!("triggered run {} ({})", run.id, href),
+        None => println!("triggered run {}", run.id),
+    }
+    Ok(run)
+}
+
+/// Polls the run until it's complete, printing every step as its status changes
+pub fn wait(api: &dyn DbtCloudApi, run_id: i64, interval: Duration) -> Result<Run, Box<dyn Error>> {
+    let mut seen: HashMap<i64, i64> = HashMap::new();
+    let mut last_status = None;
+    loop {
+        let run = api
+            .get_run(run_id)?
+            .data
+            .ok_or("dbt Cloud didn't return the run")?;
+        if last_status != Some(run.status) && !run.is_complete() {
+            println!("run {}: {}", run.id, run.status_humanized);
+            last_status = Some(run.status);
+        }
+        let total = run.run_steps.len();
+        for step in &run.run_steps {
+            if seen.get(&step.id) != Some(&step.status) {
+                seen.insert(step.id, step.status);
+                print_step(step, total);
+            }
+        }
+        if run.is_complete() {
+            return Ok(run);
+        }
+        sleep(interval);
+    }
+}
+
+fn print_step(step: &RunStep, total: usize) {
+    let status = match step.status {
+        Run::SUCCESS => step.status_humanized.green(),
+        Run::ERROR => step.status_humanized.red(),
+        Run::CANCELLED => step.status_humanized.yellow(),
+        _ => step.status_humanized.normal(),
+    };
+    let duration = step
+        .duration_humanized
+        .as_ref()
+        .map(|d| format!(" ({})", d))
+        .unwrap_or_default();
+    println!(
+        "  step {}/{} {}: {}{}",
+        step.index, total, step.name, status, duration
+    );
+}
+
+pub fn print_result(run: &Run) {
+    let message = format!("run {} finished: {}", run.id, run.status_humanized);
+    if run.is_success() {
+        println!("{}", message.green());
+    } else {
+        println!("{}", message.red());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{trigger, wait};
+    use crate::remote::{DbtCloudApi, FakeDbtCloud, Job, TriggerRun};
+
+    #[test]
+    fn trigger_and_wait() {
+        let fake = FakeDbtCloud::new(123);
+        let job = Job {
+            account_id: 123,
+            project_id: 456,
+            name: "Seed".to_string(),
+            execute_steps: vec!["dbt seed".to_string()],
+            ..Job::default()
+        };
+        let job_id = fake.create_job(&job).unwrap().data.unwrap().id.unwrap();
+
+        let run = trigger(
+            &fake,
+            job_id,
+            &TriggerRun {
+                cause: "testing".to_string(),
+                git_branch: None,
+                steps_override: Some(vec!["dbt seed --full-refresh".to_string()]),
+            },
+        )
+        .unwrap();
+        let finished = wait(&fake, run.id, Duration::from_millis(1)).unwrap();
+        assert!(finished.is_success());
+        assert_eq!(finished.job_definition_id, job_id);
+        assert_eq!(finished.run_steps[0].name, "dbt seed --full-refresh");
+        assert_eq!(finished.trigger.unwrap().cause, "testing");
+    }
+}
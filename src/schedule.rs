@@ -0,0 +1,516 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use colored::Colorize;
+
+use crate::{local::AccountRoot, remote::Job as RemoteJob};
+
+/// A five field cron expression the way dbt Cloud reads it, always in UTC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    /// 0 is Sunday
+    weekdays: BTreeSet<u32>,
+    /// Like cron, a day matches on either field when both the day and the weekday are restricted
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(format!(
+                "`{}` isn't a cron expression with five fields",
+                expression
+            ));
+        };
+        let field = |value: &str, name: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(value, min, max, names)
+                .map_err(|err| format!("`{}` has an invalid {} field: {}", expression, name, err))
+        };
+        Ok(Cron {
+            minutes: field(minute, "minute", 0, 59, &[])?,
+            hours: field(hour, "hour", 0, 23, &[])?,
+            days: field(day, "day of month", 1, 31, &[])?,
+            months: field(month, "month", 1, 12, &MONTHS)?,
+            // Both 0 and 7 are Sunday
+            weekdays: field(weekday, "day of week", 0, 7, &WEEKDAYS)?
+                .into_iter()
+                .map(|d| d % 7)
+                .collect(),
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    pub fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days.contains(&date.day());
+        let weekday = self
+            .weekdays
+            .contains(&date.weekday().num_days_from_sunday());
+        let on_day = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        };
+        on_day && self.months.contains(&date.month())
+    }
+
+    /// How many times it fires on `date`
+    pub fn runs_on(&self, date: NaiveDate) -> usize {
+        if self.matches_day(date) {
+            self.hours.len() * self.minutes.len()
+        } else {
+            0
+        }
+    }
+
+    /// When it fires from `from` up to but not including `to`, in order
+    pub fn fire_times(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let mut times = vec![];
+        let mut date = from.date();
+        while date <= to.date() {
+            if self.matches_day(date) {
+                for hour in &self.hours {
+                    for minute in &self.minutes {
+                        let time = date.and_hms_opt(*hour, *minute, 0).unwrap();
+                        if time >= from && time < to {
+                            times.push(time);
+                        }
+                    }
+                }
+            }
+            date = date + Days::new(1);
+        }
+        times
+    }
+
+    /// Averaged over a whole year so weekly and monthly schedules count for what they are
+    pub fn runs_per_day(&self) -> f64 {
+        let start = NaiveDate::from_ymd_opt(REFERENCE_YEAR, 1, 1).unwrap();
+        let total: usize = (0..REFERENCE_DAYS)
+            .map(|d| self.runs_on(start + Days::new(d)))
+            .sum();
+        total as f64 / REFERENCE_DAYS as f64
+    }
+}
+
+/// 2018 starts on a Monday and has 52 full weeks
+const REFERENCE_YEAR: i32 = 2018;
+const REFERENCE_DAYS: u64 = 52 * 7;
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// `names` stand for `min`, `min + 1` and so on, in any case, like `MON-FRI` or `jan`
+fn parse_field(value: &str, min: u32, max: u32, names: &[&str]) -> Result<BTreeSet<u32>, String> {
+    let number = |n: &str| {
+        let named = names.iter().position(|name| name.eq_ignore_ascii_case(n));
+        match named {
+            Some(i) => Ok(min + i as u32),
+            None => n
+                .parse::<u32>()
+                .map_err(|_| format!("`{}` isn't a number", n)),
+        }
+    };
+    let mut values = BTreeSet::new();
+    for part in value.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, number(step)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/15` is short for `5-<max>/15`
+            None if part.contains('/') => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(format!("`{}` isn't within {}-{}", part, min, max));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+/// Scheduled runs per day of a job as it is, or would be, in dbt Cloud
+pub fn runs_per_day(job: &RemoteJob) -> Result<f64, String> {
+    if !job.triggers.schedule {
+        return Ok(0.0);
+    }
+    Cron::parse(&job.schedule.cron).map(|c| c.runs_per_day())
+}
+
+/// Whole numbers as they are, anything else with one decimal
+pub fn format_runs(runs: f64) -> String {
+    if runs.fract().abs() < 1e-9 {
+        format!("{}", runs as i64)
+    } else {
+        format!("{:.1}", runs)
+    }
+}
+
+/// A job in dbt_cloud.yml that runs on a schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledJob {
+    /// The key under `accounts:`, `None` for single account files
+    pub account: Option<String>,
+    /// The project key, prefixed with the account when dbt_cloud.yml manages several
+    pub project: String,
+    pub environment: String,
+    pub key: String,
+    pub cron: Cron,
+}
+
+impl ScheduledJob {
+    /// `project.key`, the way `dbterra lint` names jobs
+    pub fn name(&self) -> String {
+        format!("{}.{}", self.project, self.key)
+    }
+}
+
+/// Jobs that are disabled or only run on pull requests or after other jobs aren't scheduled
+pub fn scheduled_jobs(accounts: &[AccountRoot]) -> Result<Vec<ScheduledJob>, String> {
+    let mut jobs = vec![];
+    for account in accounts {
+        for (project_key, project) in &account.yaml.projects {
+            let project_name = match &account.name {
+                Some(name) => format!("{}/{}", name, project_key),
+                None => project_key.to_string(),
+            };
+            for (key, job) in &project.jobs {
+                let schedule = match &job.schedule {
+                    Some(s) if job.enabled != Some(false) => s,
+                    _ => continue,
+                };
+                let cron = Cron::parse(&schedule.cron)
+                    .map_err(|err| format!("{}.{}: {}", project_name, key, err))?;
+                jobs.push(ScheduledJob {
+                    account: account.name.clone(),
+                    project: project_name.clone(),
+                    environment: job.environment.clone(),
+                    key: key.to_string(),
+                    cron,
+                });
+            }
+        }
+    }
+    jobs.sort_by(|a, b| (&a.project, &a.key).cmp(&(&b.project, &b.key)));
+    Ok(jobs)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Load {
+    pub jobs: usize,
+    pub runs_per_day: f64,
+}
+
+/// Scheduled runs by project and environment key, from dbt_cloud.yml alone
+pub fn report(jobs: &[ScheduledJob]) -> BTreeMap<(String, String), Load> {
+    let mut loads: BTreeMap<(String, String), Load> = BTreeMap::new();
+    for job in jobs {
+        let load = loads
+            .entry((job.project.clone(), job.environment.clone()))
+            .or_default();
+        load.jobs += 1;
+        load.runs_per_day += job.cron.runs_per_day();
+    }
+    loads
+}
+
+pub fn print_report(loads: &BTreeMap<(String, String), Load>) {
+    let mut rows: Vec<Vec<String>> = loads
+        .iter()
+        .map(|((project, environment), load)| {
+            vec![
+                project.to_string(),
+                environment.to_string(),
+                load.jobs.to_string(),
+                format_runs(load.runs_per_day),
+                format_runs(load.runs_per_day * 7.0),
+            ]
+        })
+        .collect();
+    let total_runs: f64 = loads.values().map(|l| l.runs_per_day).sum();
+    rows.push(vec![
+        "total".to_string(),
+        "".to_string(),
+        loads.values().map(|l| l.jobs).sum::<usize>().to_string(),
+        format_runs(total_runs),
+        format_runs(total_runs * 7.0),
+    ]);
+    print_table(
+        &["PROJECT", "ENVIRONMENT", "JOBS", "RUNS/DAY", "RUNS/WEEK"],
+        rows,
+    );
+}
+
+/// A scheduled run of `job`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run<'a> {
+    pub time: NaiveDateTime,
+    pub job: &'a ScheduledJob,
+}
+
+/// Every run from `from` up to but not including `to`, in order
+pub fn upcoming(jobs: &[ScheduledJob], from: NaiveDateTime, to: NaiveDateTime) -> Vec<Run<'_>> {
+    let mut runs: Vec<_> = jobs
+        .iter()
+        .flat_map(|job| {
+            job.cron
+                .fire_times(from, to)
+                .into_iter()
+                .map(move |time| Run { time, job })
+        })
+        .collect();
+    // Stable, so runs at the same time stay sorted by project and key
+    runs.sort_by_key(|r| r.time);
+    runs
+}
+
+pub fn print_upcoming(runs: &[Run]) {
+    let rows = runs
+        .iter()
+        .map(|r| {
+            vec![
+                r.time.format(TIME_FORMAT).to_string(),
+                r.job.project.to_string(),
+                r.job.environment.to_string(),
+                r.job.key.to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["TIME (UTC)", "PROJECT", "ENVIRONMENT", "JOB"], rows);
+}
+
+/// Runs per hour, including the hours without any
+pub fn histogram(
+    runs: &[Run],
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Vec<(NaiveDateTime, usize)> {
+    let mut hours = vec![];
+    let mut hour = from.date().and_hms_opt(from.hour(), 0, 0).unwrap();
+    while hour < to {
+        let next = hour + TimeDelta::hours(1);
+        let count = runs
+            .iter()
+            .filter(|r| r.time >= hour && r.time < next)
+            .count();
+        hours.push((hour, count));
+        hour = next;
+    }
+    hours
+}
+
+pub fn print_histogram(hours: &[(NaiveDateTime, usize)]) {
+    let most = hours.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+    let width = most.to_string().len();
+    for (hour, count) in hours {
+        // Scaled down when the busiest hour wouldn't fit on a line
+        let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most.max(HISTOGRAM_WIDTH)));
+        println!(
+            "{}  {:>width$}  {}",
+            hour.format(TIME_FORMAT),
+            count,
+            bar,
+            width = width
+        );
+    }
+}
+
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+const HISTOGRAM_WIDTH: usize = 60;
+
+/// `2024-05-01`, `2024-05-01 09:00` or `2024-05-01T09:00:00`, in UTC
+pub fn parse_time(value: &str) -> Result<NaiveDateTime, String> {
+    [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ]
+    .iter()
+    .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| "expected a UTC time like `2024-05-01 09:00`".to_string())
+}
+
+/// `90m`, `24h`, `7d` or `2w`
+pub fn parse_length(value: &str) -> Result<TimeDelta, String> {
+    let err = || "expected a length like `90m`, `24h`, `7d` or `2w`".to_string();
+    let unit = value.chars().last().ok_or_else(err)?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| err())?;
+    let length = match unit {
+        'm' => TimeDelta::try_minutes(amount),
+        'h' => TimeDelta::try_hours(amount),
+        'd' => TimeDelta::try_days(amount),
+        'w' => TimeDelta::try_weeks(amount),
+        _ => return Err(err()),
+    };
+    length.ok_or_else(|| format!("`{}` is too long", value))
+}
+
+fn print_table(header: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<_> = header.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.len());
+        }
+    }
+    let line = |cells: Vec<String>| {
+        let padded: Vec<_> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{:<width$}", c, width = widths[i]))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    println!(
+        "{}",
+        line(header.iter().map(|h| h.to_string()).collect()).bold()
+    );
+    for row in rows {
+        println!("{}", line(row));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{
+        format_runs, histogram, parse_length, parse_time, report, scheduled_jobs, upcoming, Cron,
+    };
+    use crate::local::Root;
+
+    #[test]
+    fn cron_expressions() {
+        assert_eq!(Cron::parse("*/15 * * * *").unwrap().runs_per_day(), 96.0);
+        assert_eq!(Cron::parse("0/10 * * * *").unwrap().runs_per_day(), 144.0);
+        assert_eq!(Cron::parse("0 9,17 * * *").unwrap().runs_per_day(), 2.0);
+        assert_eq!(
+            Cron::parse("0 9 * * 1-5").unwrap().runs_per_day() * 7.0,
+            5.0
+        );
+        assert_eq!(Cron::parse("30 6 * * 0").unwrap().runs_per_day() * 7.0, 1.0);
+        assert_eq!(Cron::parse("30 6 * * 7").unwrap().runs_per_day() * 7.0, 1.0);
+        assert_eq!(
+            Cron::parse("0 9 * * MON-FRI").unwrap(),
+            Cron::parse("0 9 * * 1-5").unwrap()
+        );
+        assert_eq!(
+            Cron::parse("0 9 1 jan,Jul sun").unwrap(),
+            Cron::parse("0 9 1 1,7 0").unwrap()
+        );
+        assert_eq!(format_runs(12.0 / 364.0 * 7.0), "0.2");
+
+        // Either the day of the month or the weekday
+        let cron = Cron::parse("0 0 1 * 1").unwrap();
+        assert!(cron.matches_day(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()));
+        assert!(cron.matches_day(NaiveDate::from_ymd_opt(2024, 5, 6).unwrap()));
+        assert!(!cron.matches_day(NaiveDate::from_ymd_opt(2024, 5, 7).unwrap()));
+
+        assert!(Cron::parse("0 9 * *").is_err());
+        assert!(Cron::parse("0 24 * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("0 9 * * MONDAY").is_err());
+    }
+
+    fn root() -> Root {
+        serde_yaml::from_str(
+            "
+projects:
+  example:
+    id: 456
+    jobs:
+      often:
+        environment: prod
+        target: production
+        steps: [dbt build]
+        schedule:
+          cron: \"*/15 * * * *\"
+      nightly:
+        environment: prod
+        target: production
+        steps: [dbt build]
+        schedule:
+          cron: \"0 2 * * *\"
+      paused:
+        environment: prod
+        target: production
+        enabled: false
+        steps: [dbt build]
+        schedule:
+          cron: \"0 3 * * *\"
+      pr:
+        environment: ci
+        target: ci
+        steps: [dbt build]
+environments:
+  prod:
+    id: 789
+  ci:
+    id: 790
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn report_by_environment() {
+        let jobs = scheduled_jobs(&root().split_accounts().unwrap()).unwrap();
+        let loads = report(&jobs);
+        assert_eq!(loads.len(), 1);
+        let load = &loads[&("example".to_string(), "prod".to_string())];
+        assert_eq!(load.jobs, 2);
+        assert_eq!(load.runs_per_day, 97.0);
+    }
+
+    #[test]
+    fn upcoming_runs() {
+        let jobs = scheduled_jobs(&root().split_accounts().unwrap()).unwrap();
+        let from = parse_time("2024-05-06 01:50").unwrap();
+        let to = from + parse_length("40m").unwrap();
+        let runs: Vec<_> = upcoming(&jobs, from, to)
+            .iter()
+            .map(|r| (r.time.format("%H:%M").to_string(), r.job.key.as_str()))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("02:00".to_string(), "nightly"),
+                ("02:00".to_string(), "often"),
+                ("02:15".to_string(), "often"),
+            ]
+        );
+
+        // Midnight to six, across the nightly run
+        let from = parse_time("2024-05-06").unwrap();
+        let to = parse_time("2024-05-06T06:00").unwrap();
+        let hours: Vec<_> = histogram(&upcoming(&jobs, from, to), from, to)
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        assert_eq!(hours, vec![4, 4, 5, 4, 4, 4]);
+
+        assert!(parse_time("tomorrow").is_err());
+        assert!(parse_length("24").is_err());
+        assert_eq!(parse_length("2w").unwrap().num_days(), 14);
+        assert!(parse_length("9999999999999w").is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use std::error::Error;
+
+use colored::{ColoredString, Colorize};
+use serde::Serialize;
+
+use crate::{
+    lookup::managed_jobs,
+    remote::{DbtCloudApi, Run},
+    workspace::Workspace,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStatus {
+    /// The key under `accounts:` when dbt_cloud.yml manages several accounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    pub project: String,
+    pub key: String,
+    pub name: String,
+    /// `None` until the job has been created in dbt Cloud
+    pub job_id: Option<i64>,
+    pub next_run: Option<String>,
+    pub runs: Vec<RunSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub id: i64,
+    pub status: String,
+    pub is_success: bool,
+    pub is_complete: bool,
+    pub started_at: Option<String>,
+    pub duration: Option<String>,
+    pub cause: Option<String>,
+}
+
+impl From<Run> for RunSummary {
+    fn from(run: Run) -> Self {
+        RunSummary {
+            id: run.id,
+            is_success: run.is_success(),
+            is_complete: run.is_complete(),
+            status: run.status_humanized,
+            started_at: run.started_at,
+            duration: run.duration_humanized,
+            cause: run.trigger.map(|t| t.cause),
+        }
+    }
+}
+
+/// Collects the most recent runs for every job declared in dbt_cloud.yml, and only those
+pub fn collect(workspace: &Workspace, limit: usize) -> Result<Vec<JobStatus>, Box<dyn Error>> {
+    let api: &dyn DbtCloudApi = workspace.client;
+    let remote_jobs = api.get_jobs()?.data.unwrap_or_default();
+    let mut statuses = vec![];
+    for managed in managed_jobs(&workspace.yaml, workspace.config) {
+        let remote = remote_jobs
+            .iter()
+            .find(|j| j.project_id == managed.job.project_id && j.name == managed.job.name);
+        let runs = match remote.and_then(|r| r.id) {
+            Some(id) => api
+                .get_runs(id, None, limit)?
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(RunSummary::from)
+                .collect(),
+            None => vec![],
+        };
+        statuses.push(JobStatus {
+            account: workspace.name.clone(),
+            project: managed.project_key,
+            key: managed.key,
+            name: managed.job.name,
+            job_id: remote.and_then(|r| r.id),
+            next_run: remote.and_then(|r| r.next_run.clone()),
+            runs,
+        });
+    }
+    Ok(statuses)
+}
+
+pub fn pretty_print(statuses: &[JobStatus]) {
+    let header = [
+        "PROJECT", "JOB", "NEXT RUN", "RUN", "STATUS", "STARTED", "DURATION", "CAUSE",
+    ];
+    let mut rows: Vec<[String; 8]> = vec![];
+    for s in statuses {
+        let project = match &s.account {
+            Some(account) => format!("{}/{}", account, s.project),
+            None => s.project.clone(),
+        };
+        let job = [
+            project,
+            s.name.clone(),
+            s.next_run.clone().unwrap_or_else(|| "-".to_string()),
+        ];
+        if s.runs.is_empty() {
+            let status = match s.job_id {
+                Some(_) => "no runs",
+                None => "not created",
+            };
+            rows.push([
+                job[0].clone(),
+                job[1].clone(),
+                job[2].clone(),
+                "-".to_string(),
+                status.to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                "-".to_string(),
+            ]);
+        }
+        for (i, r) in s.runs.iter().enumerate() {
+            // Only repeat the job for its first run so runs read as a group
+            let job = if i == 0 {
+                job.clone()
+            } else {
+                Default::default()
+            };
+            let [project, name, next_run] = job;
+            rows.push([
+                project,
+                name,
+                next_run,
+                r.id.to_string(),
+                r.status.clone(),
+                r.started_at.clone().unwrap_or_else(|| "-".to_string()),
+                r.duration.clone().unwrap_or_else(|| "-".to_string()),
+                r.cause.clone().unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+    }
+
+    let mut widths = header.map(|h| h.len());
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.len());
+        }
+    }
+    let line = |cells: Vec<ColoredString>| {
+        let padded: Vec<_> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{}{}", c, " ".repeat(widths[i] - c.chars().count())))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+
+    line(header.iter().map(|h| h.bold()).collect());
+    for row in &rows {
+        let status = &row[4];
+        let colored_status = match status.as_str() {
+            "Success" => status.green(),
+            "Error" => status.red(),
+            "Cancelled" | "not created" => status.yellow(),
+            _ => status.normal(),
+        };
+        let mut cells: Vec<ColoredString> = row.iter().map(|c| c.normal()).collect();
+        cells[4] = colored_status;
+        line(cells);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collect;
+    use crate::{
+        local::Root,
+        plan::Plan,
+        remote::{DbtCloudApi, FakeDbtCloud, Job, TriggerRun},
+        testing::{config, workspace},
+    };
+
+    #[test]
+    fn only_managed_jobs() {
+        let yaml: Root = serde_yaml::from_str(
+            "
+projects:
+  example:
+    id: 456
+    jobs:
+      seed:
+        environment: prod
+        target: production
+        steps: [dbt seed]
+      full_run:
+        environment: prod
+        target: production
+        steps: [dbt run]
+environments:
+  prod:
+    id: 789
+",
+        )
+        .unwrap();
+        let fake = FakeDbtCloud::new(123);
+        Plan::from(yaml.clone(), &fake, &config())
+            .unwrap()
+            .apply(&fake);
+        // Created by hand in another project, dbterra doesn't own it
+        fake.create_job(&Job {
+            account_id: 123,
+            project_id: 999,
+            name: "Seed".to_string(),
+            ..Job::default()
+        })
+        .unwrap();
+
+        let seed = fake
+            .get_jobs_for_project(456)
+            .unwrap()
+            .data
+            .unwrap()
+            .into_iter()
+            .find(|j| j.name == "Seed")
+            .unwrap();
+        let trigger = TriggerRun {
+            cause: "nightly".to_string(),
+            ..TriggerRun::default()
+        };
+        for _ in 0..3 {
+            fake.trigger_run(seed.id.unwrap(), &trigger).unwrap();
+        }
+
+        let workspace = workspace(yaml, &fake);
+        let statuses = collect(&workspace, 2).unwrap();
+        assert_eq!(statuses.len(), 2);
+        let seed_status = statuses.iter().find(|s| s.key == "seed").unwrap();
+        assert_eq!(seed_status.job_id, seed.id);
+        assert_eq!(seed_status.runs.len(), 2);
+        assert_eq!(seed_status.runs[0].id, 3, "newest first");
+        assert_eq!(seed_status.runs[0].cause.as_deref(), Some("nightly"));
+        assert!(statuses
+            .iter()
+            .find(|s| s.key == "full_run")
+            .unwrap()
+            .runs
+            .is_empty());
+    }
+}
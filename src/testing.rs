@@ -0,0 +1,32 @@
+use std::sync::LazyLock;
+
+use crate::{
+    config::Config,
+    local::Root,
+    remote::{DbtCloudApi, RetryPolicy},
+    workspace::Workspace,
+};
+
+static CONFIG: LazyLock<Config> = LazyLock::new(config);
+
+/// Account 123 on dbt Cloud, what `FakeDbtCloud::new(123)` stands in for in tests
+pub fn config() -> Config {
+    Config {
+        account_id: 123,
+        token: "abc123".to_string(),
+        project_id: None,
+        base_url: "https://cloud.getdbt.com".to_string(),
+        retry: RetryPolicy::default(),
+    }
+}
+
+/// A dbt_cloud.yml without `accounts:`, on top of `client`
+pub fn workspace(yaml: Root, client: &dyn DbtCloudApi) -> Workspace<'_> {
+    Workspace {
+        name: None,
+        yaml,
+        config: &CONFIG,
+        client,
+        resolved: vec![],
+    }
+}
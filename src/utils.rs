@@ -1,10 +1,15 @@
 use convert_case::{Case, Casing};
+use serde_json::Value;
 use std::collections::HashMap;
 
 use crate::config::Config;
 use crate::diff::Diff;
-use crate::local::{Environment, Job, Schedule as LocalSchedule};
-use crate::remote::{Execution, Schedule, Settings, Triggers};
+use crate::local::{
+    Environment, IgnoredField, Job, JobType, RunAfter, RunStatus, Schedule as LocalSchedule,
+};
+use crate::remote::{
+    Execution, JobCompletionCondition, JobCompletionTrigger, Run, Schedule, Settings, Triggers,
+};
 use crate::RemoteJob;
 
 /// Used to handle cases like `ml_feautres -> ML Features`
@@ -22,7 +27,80 @@ fn captialize_short_words(w: &str) -> String {
     words.join(" ")
 }
 
+impl IgnoredField {
+    /// Where the field lives in a `remote::Job`, used to keep it out of diffs
+    pub fn remote_paths(&self) -> &'static [&'static str] {
+        match self {
+            IgnoredField::Environment => &["environment_id"],
+            IgnoredField::Target => &["settings.target_name"],
+            IgnoredField::Timeout => &["execution"],
+            IgnoredField::Threads => &["settings.threads"],
+            IgnoredField::Ci => &[
+                "triggers.github_webhook",
+                "triggers.git_provider_webhook",
+                "triggers.custom_branch_only",
+                "triggers_on_draft_pr",
+                "run_compare_changes",
+                "compare_changes_flags",
+                "run_lint",
+                "errors_on_lint_failure",
+            ],
+            IgnoredField::Schedule => &["triggers.schedule", "schedule"],
+            IgnoredField::Steps => &["execute_steps"],
+            IgnoredField::GenerateDocs => &["generate_docs"],
+            IgnoredField::DeferToJobId => &["deferring_job_definition_id"],
+            IgnoredField::DeferToEnvId => &["deferring_environment_id"],
+            IgnoredField::DbtVersion => &["dbt_version"],
+            IgnoredField::Description => &["description"],
+            IgnoredField::JobType => &["job_type"],
+            IgnoredField::RunGenerateSources => &["run_generate_sources"],
+        }
+    }
+}
+
+impl RunStatus {
+    pub fn remote(&self) -> i64 {
+        match self {
+            RunStatus::Success => Run::SUCCESS,
+            RunStatus::Error => Run::ERROR,
+            RunStatus::Cancelled => Run::CANCELLED,
+        }
+    }
+}
+
+impl JobType {
+    pub fn remote(&self) -> &'static str {
+        match self {
+            JobType::Ci => "ci",
+            JobType::Merge => "merge",
+            JobType::Scheduled => "scheduled",
+            JobType::Other => "other",
+        }
+    }
+}
+
+impl RunAfter {
+    /// `job_id` is `None` while the other job only exists in dbt_cloud.yml
+    pub fn trigger(&self, project_id: i64, job_id: Option<i64>) -> JobCompletionTrigger {
+        let statuses = self.statuses.as_deref().unwrap_or(&[RunStatus::Success]);
+        JobCompletionTrigger {
+            condition: JobCompletionCondition {
+                job_id,
+                project_id,
+                statuses: statuses.iter().map(|s| s.remote()).collect(),
+            },
+        }
+    }
+}
+
 impl RemoteJob {
+    /// The job's `name`, or else the "Title Case" of its YAML key
+    pub fn name_for(key: &str, job: &Job) -> String {
+        job.name
+            .clone()
+            .unwrap_or_else(|| captialize_short_words(&key.to_case(Case::Title)))
+    }
+
     pub fn from_local_job(
         key: &str,
         job: Job,
@@ -32,23 +110,30 @@ impl RemoteJob {
         let environment = environments
             .get(&job.environment)
             .unwrap_or_else(|| panic!("no environment declared for: {}", &job.environment));
+        let name = RemoteJob::name_for(key, &job);
+        let enabled = job.enabled.unwrap_or(true);
         let has_schedule = job.schedule.is_some();
         let schedule = job.schedule.unwrap_or(LocalSchedule {
             cron: "0/10 * * * *".to_string(),
         });
         let ci = job.ci.unwrap_or_default();
-        let name = job
-            .name
-            .unwrap_or_else(|| captialize_short_words(&key.to_case(Case::Title)));
-        RemoteJob {
+        let run_on_pr = ci.run_on_pr.unwrap_or_default();
+        let job_type = job.job_type.unwrap_or(if run_on_pr {
+            JobType::Ci
+        } else if has_schedule {
+            JobType::Scheduled
+        } else {
+            JobType::Other
+        });
+        let remote = RemoteJob {
             id: None,
             account_id: config.account_id,
             project_id: config.project_id.expect("missing project_id for local job"),
-            environment_id: environment.id,
+            environment_id: environment.id(),
             name,
-            dbt_version: None,
+            dbt_version: job.dbt_version,
             triggers: Triggers {
-                github_webhook: ci.run_on_pr.unwrap_or_default(),
+                github_webhook: run_on_pr,
                 git_provider_webhook: false,
                 schedule: has_schedule,
                 custom_branch_only: ci.custom_branch_only,
@@ -65,15 +150,87 @@ impl RemoteJob {
             generate_docs: job.generate_docs.unwrap_or(false),
             schedule: Schedule::cron(&schedule.cron),
             deferring_job_definition_id: job.defer_to_job_id,
-            deferring_environment_id: job.defer_to_env_id,
+            deferring_environment_id: job.defer_to_env_id.or_else(|| {
+                let key = job.defer_to_environment.as_ref()?;
+                environments.get(key).map(|e| e.id())
+            }),
+            job_completion_trigger_condition: None,
+            job_type: job_type.remote().to_string(),
+            description: job.description.unwrap_or_default(),
+            run_generate_sources: job.run_generate_sources.unwrap_or(false),
+            triggers_on_draft_pr: ci.run_on_draft_pr.unwrap_or(false),
+            run_compare_changes: ci.compare_changes.unwrap_or(false),
+            compare_changes_flags: ci
+                .compare_changes_flags
+                .unwrap_or_else(|| RemoteJob::COMPARE_CHANGES_FLAGS.to_string()),
+            run_lint: ci.lint.unwrap_or(false),
+            errors_on_lint_failure: ci.errors_on_lint_failure.unwrap_or(true),
+            next_run: None,
+            extra: Default::default(),
+        };
+        if enabled {
+            remote
+        } else {
+            remote.deactivated()
         }
     }
 
-    pub fn merge(&self, existing: &RemoteJob) -> Self {
+    /// The same job with nothing left to start it, it can still be run by hand
+    pub fn deactivated(&self) -> Self {
+        let mut s = self.clone();
+        s.triggers.github_webhook = false;
+        s.triggers.git_provider_webhook = false;
+        s.triggers.schedule = false;
+        s.job_completion_trigger_condition = None;
+        s
+    }
+
+    pub fn merge(&self, existing: &RemoteJob, ignored: &[IgnoredField]) -> Self {
         let mut s = self.clone();
 
         // Always set ID to existing one since that won't change
         s.id = existing.id;
+        s.extra = existing.extra.clone();
+
+        // Fields tweaked outside of dbterra on purpose keep whatever they are set to remotely
+        for field in ignored {
+            match field {
+                IgnoredField::Environment => s.environment_id = existing.environment_id,
+                IgnoredField::Target => {
+                    s.settings.target_name = existing.settings.target_name.clone()
+                }
+                IgnoredField::Timeout => s.execution = existing.execution.clone(),
+                IgnoredField::Threads => s.settings.threads = existing.settings.threads,
+                IgnoredField::Ci => {
+                    s.triggers.github_webhook = existing.triggers.github_webhook;
+                    s.triggers.git_provider_webhook = existing.triggers.git_provider_webhook;
+                    s.triggers.custom_branch_only = existing.triggers.custom_branch_only;
+                    s.triggers_on_draft_pr = existing.triggers_on_draft_pr;
+                    s.run_compare_changes = existing.run_compare_changes;
+                    s.compare_changes_flags = existing.compare_changes_flags.clone();
+                    s.run_lint = existing.run_lint;
+                    s.errors_on_lint_failure = existing.errors_on_lint_failure;
+                }
+                IgnoredField::Schedule => {
+                    s.triggers.schedule = existing.triggers.schedule;
+                    s.schedule = existing.schedule.clone();
+                }
+                IgnoredField::Steps => s.execute_steps = existing.execute_steps.clone(),
+                IgnoredField::GenerateDocs => s.generate_docs = existing.generate_docs,
+                IgnoredField::DeferToJobId => {
+                    s.deferring_job_definition_id = existing.deferring_job_definition_id
+                }
+                IgnoredField::DeferToEnvId => {
+                    s.deferring_environment_id = existing.deferring_environment_id
+                }
+                IgnoredField::DbtVersion => s.dbt_version = existing.dbt_version.clone(),
+                IgnoredField::Description => s.description = existing.description.clone(),
+                IgnoredField::JobType => s.job_type = existing.job_type.clone(),
+                IgnoredField::RunGenerateSources => {
+                    s.run_generate_sources = existing.run_generate_sources
+                }
+            }
+        }
 
         // If we aren't set to schedule, use the existing values for schedule no matter what
         if !s.triggers.schedule {
@@ -83,16 +240,21 @@ impl RemoteJob {
         s
     }
 
+    /// The job as JSON without `extra`, dbterra can't tell whether those changed on purpose
+    fn known_fields(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap();
+        if let Value::Object(map) = &mut value {
+            map.retain(|key, _| !self.extra.contains_key(key));
+        }
+        value
+    }
+
     pub fn diff<'a>(&'a self, job: &'a RemoteJob) -> Diff {
-        let v1 = serde_json::to_value(self).unwrap();
-        let v2 = serde_json::to_value(job).unwrap();
-        Diff::from(v1, v2)
+        Diff::from(self.known_fields(), job.known_fields())
     }
 
     pub fn new_diff<'a>(&'a self, job: &'a RemoteJob) -> Diff {
-        let v1 = serde_json::to_value(self).unwrap();
-        let v2 = serde_json::to_value(job).unwrap();
-        Diff::from_new(v1, v2)
+        Diff::from_new(self.known_fields(), job.known_fields())
     }
 }
 
@@ -102,8 +264,10 @@ mod tests {
 
     use crate::{
         config::Config,
-        local::{Environment, Job as LocalJob},
-        remote::{Date, Execution, Job as RemoteJob, Schedule, Settings, Time, Triggers},
+        local::{Environment, IgnoredField, Job as LocalJob},
+        remote::{
+            Date, Execution, Job as RemoteJob, RetryPolicy, Schedule, Settings, Time, Triggers,
+        },
     };
 
     #[test]
@@ -113,14 +277,22 @@ mod tests {
             token: "abc123".to_string(),
             project_id: Some(456),
             base_url: "https://cloud.getdbt.com".to_string(),
+            retry: RetryPolicy::default(),
         };
         let mut environments = HashMap::new();
-        environments.insert("test".to_string(), Environment { id: 789 });
+        environments.insert(
+            "test".to_string(),
+            Environment {
+                id: Some(789),
+                ..Environment::default()
+            },
+        );
         let local_job = LocalJob {
             steps: vec!["dbt run".to_string()],
             environment: "test".to_string(),
             target: "production".to_string(),
             name: None,
+            enabled: None,
             timeout: None,
             threads: None,
             ci: None,
@@ -128,12 +300,21 @@ mod tests {
             generate_docs: None,
             defer_to_job_id: None,
             defer_to_env_id: None,
+            defer_to_job: None,
+            defer_to_environment: None,
+            ignore_changes: None,
+            notifications: None,
+            run_after: None,
+            dbt_version: None,
+            job_type: None,
+            description: None,
+            run_generate_sources: None,
         };
         let expected_remote = RemoteJob {
             id: None,
             account_id: config.account_id,
             project_id: config.project_id.unwrap(),
-            environment_id: environments.get("test").unwrap().id,
+            environment_id: environments.get("test").unwrap().id(),
             name: "Test".to_string(), // converted from key
             dbt_version: None,
             triggers: Triggers {
@@ -165,6 +346,17 @@ mod tests {
                 },
             },
             execution: Execution { timeout_seconds: 0 },
+            job_completion_trigger_condition: None,
+            job_type: "other".to_string(),
+            description: "".to_string(),
+            run_generate_sources: false,
+            triggers_on_draft_pr: false,
+            run_compare_changes: false,
+            compare_changes_flags: "--select state:modified".to_string(),
+            run_lint: false,
+            errors_on_lint_failure: true,
+            next_run: None,
+            extra: Default::default(),
         };
         assert_eq!(
             RemoteJob::from_local_job("test", local_job, &config, &environments),
@@ -179,14 +371,22 @@ mod tests {
             token: "abc123".to_string(),
             project_id: Some(456),
             base_url: "https://cloud.getdbt.com".to_string(),
+            retry: RetryPolicy::default(),
         };
         let mut environments = HashMap::new();
-        environments.insert("test".to_string(), Environment { id: 789 });
+        environments.insert(
+            "test".to_string(),
+            Environment {
+                id: Some(789),
+                ..Environment::default()
+            },
+        );
         let local_job = LocalJob {
             steps: vec!["dbt run".to_string()],
             environment: "test".to_string(),
             target: "production".to_string(),
             name: None,
+            enabled: None,
             timeout: None,
             threads: None,
             ci: None,
@@ -194,6 +394,15 @@ mod tests {
             generate_docs: None,
             defer_to_job_id: None,
             defer_to_env_id: None,
+            defer_to_job: None,
+            defer_to_environment: None,
+            ignore_changes: None,
+            notifications: None,
+            run_after: None,
+            dbt_version: None,
+            job_type: None,
+            description: None,
+            run_generate_sources: None,
         };
         let converted_job = RemoteJob::from_local_job(
             "test_some_snake_case_thing",
@@ -211,14 +420,22 @@ mod tests {
             token: "abc123".to_string(),
             project_id: Some(456),
             base_url: "https://cloud.getdbt.com".to_string(),
+            retry: RetryPolicy::default(),
         };
         let mut environments = HashMap::new();
-        environments.insert("test".to_string(), Environment { id: 789 });
+        environments.insert(
+            "test".to_string(),
+            Environment {
+                id: Some(789),
+                ..Environment::default()
+            },
+        );
         let local_job = LocalJob {
             steps: vec!["dbt run".to_string()],
             environment: "test".to_string(),
             target: "production".to_string(),
             name: Some("My Test Job".to_string()),
+            enabled: None,
             timeout: None,
             threads: None,
             ci: None,
@@ -226,6 +443,15 @@ mod tests {
             generate_docs: None,
             defer_to_job_id: None,
             defer_to_env_id: None,
+            defer_to_job: None,
+            defer_to_environment: None,
+            ignore_changes: None,
+            notifications: None,
+            run_after: None,
+            dbt_version: None,
+            job_type: None,
+            description: None,
+            run_generate_sources: None,
         };
         let converted_job = RemoteJob::from_local_job(
             "test_some_snake_case_thing",
@@ -235,4 +461,119 @@ mod tests {
         );
         assert_eq!(&converted_job.name, "My Test Job");
     }
+
+    #[test]
+    fn ci_settings() {
+        let config = Config {
+            account_id: 123,
+            token: "abc123".to_string(),
+            project_id: Some(456),
+            base_url: "https://cloud.getdbt.com".to_string(),
+            retry: RetryPolicy::default(),
+        };
+        let mut environments = HashMap::new();
+        environments.insert(
+            "test".to_string(),
+            Environment {
+                id: Some(789),
+                ..Environment::default()
+            },
+        );
+        let local_job: LocalJob = serde_yaml::from_str(
+            "
+environment: test
+target: ci
+steps: [dbt build]
+ci:
+  run_on_pr: true
+  run_on_draft_pr: true
+  compare_changes: true
+  lint: true
+  errors_on_lint_failure: false
+",
+        )
+        .unwrap();
+        let converted_job = RemoteJob::from_local_job("pr", local_job, &config, &environments);
+        assert_eq!(converted_job.job_type, "ci");
+        assert!(converted_job.triggers_on_draft_pr);
+        assert!(converted_job.run_compare_changes);
+        assert_eq!(
+            converted_job.compare_changes_flags,
+            "--select state:modified"
+        );
+        assert!(converted_job.run_lint);
+        assert!(!converted_job.errors_on_lint_failure);
+    }
+
+    #[test]
+    fn ignored_description_stays_remote() {
+        let config = Config {
+            account_id: 123,
+            token: "abc123".to_string(),
+            project_id: Some(456),
+            base_url: "https://cloud.getdbt.com".to_string(),
+            retry: RetryPolicy::default(),
+        };
+        let mut environments = HashMap::new();
+        environments.insert(
+            "prod".to_string(),
+            Environment {
+                id: Some(789),
+                ..Environment::default()
+            },
+        );
+        let local_job: LocalJob = serde_yaml::from_str(
+            "
+environment: prod
+target: prod
+steps: [dbt build]
+description: Builds everything
+",
+        )
+        .unwrap();
+        let local = RemoteJob::from_local_job("nightly", local_job, &config, &environments);
+        let mut existing = local.clone();
+        existing.description = "Maintained by the finance team".to_string();
+
+        let merged = local.merge(&existing, &[IgnoredField::Description]);
+        assert_eq!(merged.description, existing.description);
+        assert!(!merged.diff(&existing).has_changes());
+        assert!(local.merge(&existing, &[]).diff(&existing).has_changes());
+    }
+
+    #[test]
+    fn unknown_fields_survive_updates() {
+        let existing: RemoteJob = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "account_id": 123,
+            "project_id": 456,
+            "environment_id": 789,
+            "name": "Test",
+            "dbt_version": null,
+            "triggers": {"github_webhook": false, "git_provider_webhook": false, "schedule": false},
+            "execute_steps": ["dbt run"],
+            "settings": {"threads": 4, "target_name": "production"},
+            "state": 1,
+            "generate_docs": false,
+            "deferring_job_definition_id": null,
+            "deferring_environment_id": null,
+            "schedule": {"cron": "0 * * * *", "date": {"type": "every_day"}, "time": {"type": "every_hour", "interval": 1}},
+            "execution": {"timeout_seconds": 0},
+            "some_new_setting": {"enabled": true},
+        }))
+        .unwrap();
+        assert!(existing.extra.contains_key("some_new_setting"));
+
+        let mut local = existing.clone();
+        local.extra.clear();
+        local.settings.threads = 8;
+        let merged = local.merge(&existing, &[]);
+        let sent = serde_json::to_value(&merged).unwrap();
+        assert_eq!(sent["some_new_setting"]["enabled"], true);
+
+        let diff = merged.diff(&existing);
+        assert!(diff.has_changes());
+        local.settings.threads = 4;
+        assert!(!local.diff(&existing).has_changes());
+    }
 }
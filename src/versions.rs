@@ -0,0 +1,117 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use crate::{local::Root, remote::DbtCloudApi};
+
+/// Always available, dbt Cloud picks the actual version
+pub const RELEASE_TRACKS: [&str; 4] = ["latest", "compatible", "extended", "versionless"];
+
+/// Makes sure every pinned `dbt_version` is one dbt Cloud offers. Returns a warning for each job
+/// pinned to something other than its environment's version.
+pub fn check(yaml: &Root, client: &dyn DbtCloudApi) -> Result<Vec<String>, String> {
+    let mut pinned: Vec<_> = yaml
+        .projects
+        .iter()
+        .flat_map(|(project_key, project)| {
+            project.jobs.iter().filter_map(move |(key, job)| {
+                let version = job.dbt_version.as_ref()?;
+                Some((project_key, project.id(), key, job, version))
+            })
+        })
+        .collect();
+    if pinned.is_empty() {
+        return Ok(vec![]);
+    }
+    pinned.sort_by_key(|(project_key, _, key, _, _)| (*project_key, *key));
+
+    let available: Vec<_> = client
+        .get_dbt_versions()
+        .map_err(|err| format!("failed to get dbt versions: {}", err))?
+        .data
+        .unwrap_or_default()
+        .into_iter()
+        .map(|v| v.name)
+        .collect();
+    let mut environment_versions = HashMap::new();
+    let mut warnings = vec![];
+    for (project_key, project_id, key, job, version) in pinned {
+        if !RELEASE_TRACKS.contains(&version.as_str()) && !available.contains(version) {
+            return Err(format!(
+                "{}.{}: dbt_version `{}` isn't available, pick one of {}",
+                project_key,
+                key,
+                version,
+                available.join(", ")
+            ));
+        }
+        let environment = match yaml.environments.get(&job.environment) {
+            Some(e) => e,
+            None => continue,
+        };
+        if let Entry::Vacant(entry) = environment_versions.entry(environment.id()) {
+            let remote = client
+                .get_environment(project_id, environment.id())
+                .map_err(|err| {
+                    format!("failed to get environment {}: {}", environment.id(), err)
+                })?;
+            entry.insert(remote.data.and_then(|e| e.dbt_version));
+        }
+        if let Some(Some(environment_version)) = environment_versions.get(&environment.id()) {
+            if environment_version != version {
+                warnings.push(format!(
+                    "{}.{} is pinned to dbt {} but its environment `{}` uses {}",
+                    project_key, key, version, job.environment, environment_version
+                ));
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::{
+        local::Root,
+        remote::{Environment, FakeDbtCloud},
+    };
+
+    fn root(version: &str) -> Root {
+        serde_yaml::from_str(&format!(
+            "
+projects:
+  example:
+    id: 456
+    jobs:
+      full_run:
+        environment: prod
+        target: production
+        steps: [dbt run]
+        dbt_version: {}
+environments:
+  prod:
+    id: 789
+",
+            version
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn pinned_versions() {
+        let fake = FakeDbtCloud::new(123);
+        fake.add_environment(Environment {
+            id: Some(789),
+            project_id: 456,
+            dbt_version: Some("1.7.0-latest".to_string()),
+            ..Environment::default()
+        });
+        assert!(check(&root("1.7.0-latest"), &fake).unwrap().is_empty());
+        assert_eq!(
+            check(&root("versionless"), &fake).unwrap(),
+            vec!["example.full_run is pinned to dbt versionless but its environment `prod` uses 1.7.0-latest"]
+        );
+        let err = check(&root("0.21.0"), &fake).unwrap_err();
+        assert!(err.starts_with("example.full_run: dbt_version `0.21.0` isn't available"));
+    }
+}
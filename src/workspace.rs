@@ -0,0 +1,22 @@
+use crate::{config::Config, local::Root, remote::DbtCloudApi, resolve::Resolved};
+
+/// Everything needed to work with one dbt Cloud account from dbt_cloud.yml
+pub struct Workspace<'a> {
+    /// The key under `accounts:`, `None` for single account files
+    pub name: Option<String>,
+    pub yaml: Root,
+    pub config: &'a Config,
+    pub client: &'a dyn DbtCloudApi,
+    /// Ids dbt_cloud.yml left out, already filled in on `yaml`
+    pub resolved: Vec<Resolved>,
+}
+
+impl<'a> Workspace<'a> {
+    /// How the account is shown in output, e.g. `eu/analytics` for a project
+    pub fn prefix(&self, value: &str) -> String {
+        match &self.name {
+            Some(name) => format!("{}/{}", name, value),
+            None => value.to_string(),
+        }
+    }
+}